@@ -1,14 +1,16 @@
 #![allow(unused_variables)]
 #![allow(unused_imports)]
 
-use std::{sync::{Arc, Mutex}, cell::Ref};
+use std::collections::HashMap;
+use std::{sync::{Arc, Mutex, atomic::{AtomicU32, Ordering}}, cell::Ref};
 use std::cell::RefCell;
 use std::io::{Read, Write};
 
 pub mod fs;
+pub mod streams;
 pub mod utils;
 
-use bls_common::{http::{HttpResponse, HttpRequest}, ipfs::{IPFSCommand, client::IPFSClient}, s3::{S3Client, S3Command}};
+use bls_common::{http::{HttpClient, HttpConfig, HttpError, HttpResponse, HttpRequest}, ipfs::{IPFSCommand, IPFSConfig, client::IPFSClient}, permissions::Permissions, quota::{Extension, QuotaConfig, QuotaUsage, Quotas, QUOTA_EXCEEDED_STATUS}, retry::{CallResult, RetryPolicy}, s3::{S3Client, S3ClientConfig, S3Command, S3Error}, time};
 
 use serde::{Deserialize, Serialize};
 use js_sys::{Map, Object, Reflect, WebAssembly};
@@ -64,10 +66,122 @@ export type BlocklessConfig = {
     readonly env?: Record<string, string>;
     /** Preopened directories. */
     readonly preopens?: Record<string, string>;
-    /** Additional permissions. */
+    /**
+     * Additional permissions; validated when the runtime is created.
+     * Outbound http rules use the form `[!][METHODS ]scheme://host[:port][/path]`,
+     * e.g. `GET,HEAD https://*.example.com/api/**` or `!https://example.com/admin/**`.
+     * S3 credential profiles are restricted to buckets with `[!]s3://profile/bucket`,
+     * e.g. `s3://media/*` or `!s3://media/private-*`; listing buckets requires a `*` bucket.
+     * IPFS RPC commands are allowed with `[!]ipfs://command`, e.g. `ipfs://files` (every `files/*` command),
+     * `ipfs://version`, `ipfs://pin/remote` (pins at the configured pinning service) or `ipfs://*`.
+     */
     readonly permissions?: string[];
     /** The in-memory filesystem that should be used. */
     readonly fs?: MemFS;
+    /** Outbound http settings. */
+    readonly http?: {
+        /** Default timeout (in milliseconds) for `http_call` requests; requests may override it. */
+        readonly timeout?: number;
+        /**
+         * Enables the response cache for `GET` requests (honors `Cache-Control`, `ETag` and `Last-Modified`).
+         * Inspect it with `httpCacheStats()` and clear it with `clearHttpCache()`.
+         */
+        readonly cache?: {
+            /** Upper bound of the cached responses in bytes (default: 16MiB). */
+            readonly maxBytes?: number;
+        };
+        /**
+         * How requests are sent (default: `live`):
+         * - `mock` answers from `routes` (first match wins; a url ending with `*` matches as prefix),
+         * - `record` sends requests over the network and keeps the exchanges - save `httpRecording()` as fixture file,
+         * - `replay` answers from such `fixtures`; unknown requests fail unless `passthrough` is set.
+         */
+        readonly transport?:
+            | { readonly mode: "live" }
+            | { readonly mode: "mock"; readonly routes: { method?: string; url: string; status?: number; headers?: Record<string, string>; body?: string }[] }
+            | { readonly mode: "record" }
+            | { readonly mode: "replay"; readonly fixtures: unknown[]; readonly passthrough?: boolean };
+    };
+    /** S3 settings. */
+    readonly s3?: {
+        /**
+         * Credential profiles held by the runtime; guests reference them by id (`S3Config.profile`)
+         * and never see the keys. Every profile needs `s3://` permissions for the buckets it may touch.
+         */
+        readonly profiles?: Record<string, {
+            /** `mem://<name>` keeps the buckets in memory for the lifetime of the runtime (testing). */
+            readonly endpoint: string;
+            readonly region?: string;
+            readonly accessKey: string;
+            readonly secretKey: string;
+            readonly sessionToken?: string;
+            /** `path` (default) or `virtual_hosted` (`bucket.endpoint-host`). */
+            readonly addressingStyle?: "path" | "virtual_hosted";
+        }>;
+    };
+    /** IPFS settings; guests need `ipfs://` permissions for the commands they call. */
+    readonly ipfs?: {
+        /** Base url of the node's RPC API, without `/api/v0` (default: `http://127.0.0.1:5001`). */
+        readonly apiUrl?: string;
+        /** Sent as `Authorization` header with every request to the node (never to the gateway). */
+        readonly auth?:
+            | { readonly type: "bearer"; readonly token: string }
+            | { readonly type: "basic"; readonly username: string; readonly password?: string };
+        /** Timeout (in milliseconds) of every request to the node. */
+        readonly timeout?: number;
+        /**
+         * Trustless gateway (e.g. `https://trustless-gateway.link`) serving `cat` and `get` instead of the node;
+         * every block is verified against its CID. Other commands (except remote pins) fail while it is set.
+         */
+        readonly gatewayUrl?: string;
+        /** Sent as `Authorization` header with every request to the gateway. */
+        readonly gatewayAuth?:
+            | { readonly type: "bearer"; readonly token: string }
+            | { readonly type: "basic"; readonly username: string; readonly password?: string };
+        /** IPFS Pinning Services API endpoint serving the `pin/remote/*` commands. */
+        readonly pinningService?: {
+            /** Base url of the API, without `/pins` (e.g. `https://api.pinata.cloud/psa`). */
+            readonly endpoint: string;
+            /** Sent as bearer token. */
+            readonly accessToken: string;
+        };
+    };
+    /**
+     * Retry policy of outbound http, s3 and ipfs calls; by default every call is attempted once.
+     * Http requests and s3 configs may carry their own policy, capped at `maxAttempts` and `maxBackoff` of this one.
+     */
+    readonly retry?: {
+        /** Total number of attempts, including the first one (default: 1). */
+        readonly maxAttempts?: number;
+        /** Delay (in milliseconds) before the first retry (default: 100). */
+        readonly initialBackoff?: number;
+        /** Upper bound (in milliseconds) of the delay between attempts (default: 10000). */
+        readonly maxBackoff?: number;
+        /** Factor applied to the delay after every attempt (default: 2). */
+        readonly multiplier?: number;
+        /** Randomize every delay between zero and the computed backoff (default: true). */
+        readonly jitter?: boolean;
+        /** Response status codes that are retried (default: 408, 429, 500, 502, 503, 504). */
+        readonly retryableStatus?: number[];
+        /** Only retry idempotent calls, e.g. never a `POST` (default: true). */
+        readonly idempotentOnly?: boolean;
+    };
+    /**
+     * Per-instance quotas of the `http`, `s3` and `ipfs` extensions; unset limits are not enforced.
+     * Rejected calls fail in the guest with a quota-exceeded error; see `quotaUsage()`.
+     */
+    readonly quotas?: Partial<Record<"http" | "s3" | "ipfs", {
+        /** Total number of calls; every retry counts as a call. */
+        readonly maxCalls?: number;
+        /** Number of calls in flight at the same time. */
+        readonly maxConcurrent?: number;
+        /** Total bytes delivered to the guest. */
+        readonly maxBytesIn?: number;
+        /** Total bytes sent by the guest. */
+        readonly maxBytesOut?: number;
+        /** Token bucket: up to `burst` calls (or retries), refilled at `perSecond` calls per second. */
+        readonly rate?: { readonly perSecond: number; readonly burst: number };
+    }>>;
 };
 "#;
 
@@ -83,14 +197,21 @@ extern "C" {
 //     WasiError, WasiFunctionEnv, WasiEnvBuilder, runtime::{task_manager::WebThreadPool, web::WebRuntime}
 // };
 
+static NEXT_INSTANCE_ID: AtomicU32 = AtomicU32::new(1);
+
 #[wasm_bindgen]
 pub struct Blockless {
+    id: u32,
     store: Store,
     stdout: Pipe,
     stdin: Pipe,
     stderr: Pipe,
     wasi_env: WasiFunctionEnv,
-    permissions: Vec<String>,
+    permissions: Permissions,
+    http_client: HttpClient,
+    ipfs_client: IPFSClient,
+    s3_client: S3Client,
+    quotas: Quotas,
     module: Option<Module>,
     instance: Option<Instance>,
     // host exports may call into guest guest imports - which may not be set.
@@ -177,6 +298,21 @@ impl Blockless {
                     .collect::<Result<Vec<String>, JsValue>>()?
             }
         };
+        let permissions = Permissions::parse(&permissions)
+            .map_err(|e| js_sys::Error::new(&e))?;
+        let retry = utils::get_config_section::<RetryPolicy>(&config, "retry")?;
+        let quotas = Quotas::new(utils::get_config_section::<QuotaConfig>(&config, "quotas")?);
+        // retries are charged against the quotas like calls of their own
+        let http_client = HttpClient::new(utils::get_config_section::<HttpConfig>(&config, "http")?)
+            .with_retry(retry.clone())
+            .with_quotas(quotas.clone());
+        let ipfs_client = IPFSClient::new(utils::get_config_section::<IPFSConfig>(&config, "ipfs")?)
+            .map_err(|e| js_sys::Error::new(&e))?
+            .with_retry(retry.clone())
+            .with_quotas(quotas.clone());
+        let s3_client = S3Client::new(utils::get_config_section::<S3ClientConfig>(&config, "s3")?)
+            .with_retry(retry.clone())
+            .with_quotas(quotas.clone());
 
         let fs = {
             let fs = js_sys::Reflect::get(&config, &"fs".into())?;
@@ -206,12 +342,17 @@ impl Blockless {
             .map_err(|e| js_sys::Error::new(&format!("Failed to create the WasiEnv: {}`", e)))?;
 
         Ok(Blockless{
+            id: NEXT_INSTANCE_ID.fetch_add(1, Ordering::SeqCst),
             store,
             stdout,
             stdin,
             stderr,
             wasi_env,
             permissions,
+            http_client,
+            ipfs_client,
+            s3_client,
+            quotas,
             module: None,
             instance: None,
             exports: Arc::new(Mutex::new(RefCell::new(None))),
@@ -264,16 +405,22 @@ impl Blockless {
     fn get_host_imports(&mut self) -> Result<Imports, JsValue> {
         #[derive(Clone)]
         struct Env {
+            instance_id: u32,
             exports: Arc<Mutex<RefCell<Option<Exports>>>>,
-            permissions: Vec<String>,
+            permissions: Permissions,
+            quotas: Quotas,
+            http_client: HttpClient,
             ipfs_client: IPFSClient,
             s3_client: S3Client,
         }
         let env = FunctionEnv::new(&mut self.store, Env {
+            instance_id: self.id,
             exports: self.exports.clone(),
             permissions: self.permissions.clone(),
-            ipfs_client: IPFSClient::default(),
-            s3_client: S3Client::default(),
+            quotas: self.quotas.clone(),
+            http_client: self.http_client.clone(),
+            ipfs_client: self.ipfs_client.clone(),
+            s3_client: self.s3_client.clone(),
         });
 
         fn host_log(ctx: FunctionEnvMut<Env>, ptr: u32, len: u32) {
@@ -311,17 +458,14 @@ impl Blockless {
             let http_req = serde_json::from_slice::<HttpRequest>(&buf).expect("failed to deserialize http request");
             console_log!("http_call: http_request called: {}", http_req); // TODO trace
 
-            if !http_req.valid_permissions(&ctx.data().permissions) {
-                console_error!("invalid permissions");
-                let data = serde_json::to_vec(&Err::<HttpResponse, String>("invalid permissions".into()))
-                    .expect("failed to serialize module call response");
-                // allocate memory for size of result and return back pointer to the allocated memory
-                // first 4 bytes are the length of the result
-                memory.view(&ctx.as_store_ref()).write(ptr as u64, &(data.len() as u32).to_le_bytes()).expect("failed to write data length to memory");
-                // next bytes are the actual result
-                memory.view(&ctx.as_store_ref()).write((ptr + 4) as u64, &data).expect("failed to write data to memory");
-                return ptr as u32;
-            }
+            // NOTE: quotas are enforced before any work is spawned; rejected calls never reach `http_callback`
+            let permit = match ctx.data().quotas.acquire(Extension::Http, len as u64, time::now_secs()) {
+                Ok(permit) => permit,
+                Err(err) => {
+                    console_error!("http_call rejected: {}", err);
+                    return QUOTA_EXCEEDED_STATUS;
+                }
+            };
 
             let boxed_ctx_ref: Box<FunctionEnvMut<Env>> = Box::new(ctx);
             let static_ctx_ref: &'static mut FunctionEnvMut<Env> = unsafe { std::mem::transmute(Box::leak(boxed_ctx_ref)) };
@@ -351,18 +495,95 @@ impl Blockless {
                     .clone()
                     .into();
 
-                let http_call_response = match http_req.request().await {
-                    Ok(response) => HttpResponse::from_reqwest(response)
-                        .await
-                        .map(|response| serde_json::to_vec(&response).map_err(|err| err.to_string()))
-                        .map_err(|err| err.to_string())
-                        .expect("failed to serialize module call response"),
-                    Err(err) => {
-                        console_error!("Error while running start function: {}", err);
-                        Err(err.to_string())
-                    }
+                // NOTE: errors (including permission errors) are delivered through `http_callback`
+                let http_call_response = if !http_req.valid_permissions(&static_ctx_ref.data().permissions) {
+                    Err(HttpError::PermissionDenied)
+                } else if http_req.stream {
+                    let instance_id = static_ctx_ref.data().instance_id;
+                    static_ctx_ref.data().http_client.fetch_stream(&http_req).await
+                        .map(|(mut response, body)| {
+                            response.body_handle = Some(streams::insert(instance_id, body));
+                            serde_json::to_vec(&response).expect("failed to serialize http response")
+                        })
+                } else {
+                    static_ctx_ref.data().http_client.fetch(&http_req).await
+                        .map(|response| serde_json::to_vec(&response).expect("failed to serialize http response"))
                 };
+                if let Err(err) = &http_call_response {
+                    console_error!("Error while running http_call: {}", err);
+                }
                 let data = serde_json::to_vec(&http_call_response).expect("failed to serialize module call response");
+                permit.record_bytes_in(data.len() as u64);
+                let result_ptr = utils::encode_data_to_memory(&memory_obj, &alloc_func, &data);
+
+                match http_callback.call2(&JsValue::undefined(), &JsValue::from(result_ptr), &JsValue::from(callback_id)) {
+                    Ok(_val) => console_log!("http_callback called successfully"),
+                    Err(err) => console_error!("Error while running http_callback {}", err.as_string().unwrap_or_default()),
+                };
+
+                // manually deallocate memory
+                unsafe {
+                    let _reclaimed = Box::from_raw(static_ctx_ref);
+                }
+            });
+            0
+        }
+
+        /// Reads the next chunk (up to `max_len` bytes) of a streamed response body.
+        /// The chunk is delivered through `http_callback`; an empty chunk marks the end of the body.
+        fn http_read_body(ctx: FunctionEnvMut<Env>, handle: u32, max_len: u32, callback_id: u64) -> u32 {
+            let exports = {
+                let binding = ctx.data().exports.lock().unwrap();
+                let exports = binding.borrow().to_owned().expect("exports should have been set");
+                exports
+            };
+
+            // body reads are part of the original call; only the received bytes are limited
+            if let Err(err) = ctx.data().quotas.check_bytes_in(Extension::Http) {
+                console_error!("http_read_body rejected: {}", err);
+                return QUOTA_EXCEEDED_STATUS;
+            }
+
+            let boxed_ctx_ref: Box<FunctionEnvMut<Env>> = Box::new(ctx);
+            let static_ctx_ref: &'static mut FunctionEnvMut<Env> = unsafe { std::mem::transmute(Box::leak(boxed_ctx_ref)) };
+            wasm_bindgen_futures::spawn_local(async move {
+                // NOTE: convert callbacks to wasm_bindgen types - since return values do not seem to work!
+                let memory_obj: WebAssembly::Memory = exports
+                    .get_extern("memory")
+                    .expect("memory export wasn't found")
+                    .to_vm_extern()
+                    .as_jsvalue(&static_ctx_ref.as_store_ref())
+                    .clone()
+                    .into();
+                let http_callback: js_sys::Function = exports
+                    .get_function("http_callback")
+                    .expect("http_callback function not found")
+                    .to_vm_extern()
+                    .as_jsvalue(&static_ctx_ref.as_store_ref())
+                    .clone()
+                    .into();
+                let alloc_func: js_sys::Function = exports
+                    .get_function("alloc")
+                    .expect("alloc function not found")
+                    .to_vm_extern()
+                    .as_jsvalue(&static_ctx_ref.as_store_ref())
+                    .clone()
+                    .into();
+
+                let instance_id = static_ctx_ref.data().instance_id;
+                let read_response = match streams::take(instance_id, handle) {
+                    Ok(mut body) => {
+                        let chunk = body.read(max_len as usize).await;
+                        streams::restore(instance_id, handle, body);
+                        chunk
+                    }
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = &read_response {
+                    console_error!("Error while running http_read_body: {}", err);
+                }
+                let data = serde_json::to_vec(&read_response).expect("failed to serialize module call response");
+                static_ctx_ref.data().quotas.record_bytes_in(Extension::Http, data.len() as u64);
                 let result_ptr = utils::encode_data_to_memory(&memory_obj, &alloc_func, &data);
 
                 match http_callback.call2(&JsValue::undefined(), &JsValue::from(result_ptr), &JsValue::from(callback_id)) {
@@ -378,6 +599,11 @@ impl Blockless {
             0
         }
 
+        /// Closes a streamed response body; returns non-zero if the handle is unknown.
+        fn http_close(ctx: FunctionEnvMut<Env>, handle: u32) -> u32 {
+            if streams::close(ctx.data().instance_id, handle) { 0 } else { 1 }
+        }
+
         fn ipfs_call(ctx: FunctionEnvMut<Env>, ptr: u32, len: u32, callback_id: u64) -> u32 {
             let exports = {
                 let binding = ctx.data().exports.lock().unwrap();
@@ -400,6 +626,15 @@ impl Blockless {
             let ipfs_command = serde_json::from_slice::<IPFSCommand>(&buf).expect("failed to deserialize http request");
             console_log!("ipfs_call: ipfs_request called: {}", ipfs_command); // TODO trace
 
+            // NOTE: quotas are enforced before any work is spawned; rejected calls never reach `ipfs_callback`
+            let permit = match ctx.data().quotas.acquire(Extension::Ipfs, len as u64, time::now_secs()) {
+                Ok(permit) => permit,
+                Err(err) => {
+                    console_error!("ipfs_call rejected: {}", err);
+                    return QUOTA_EXCEEDED_STATUS;
+                }
+            };
+
             let boxed_ctx_ref: Box<FunctionEnvMut<Env>> = Box::new(ctx);
             let static_ctx_ref: &'static mut FunctionEnvMut<Env> = unsafe { std::mem::transmute(Box::leak(boxed_ctx_ref)) };
             wasm_bindgen_futures::spawn_local(async move {
@@ -428,17 +663,23 @@ impl Blockless {
                     .clone()
                     .into();
 
-                let ipfs_call_response = match ipfs_command.exec(&static_ctx_ref.data().ipfs_client).await {
-                    Ok(response) => Ok(response),
-                    Err(err) => {
-                        console_error!("Error while running ipfs_command.exec: {}", err);
-                        Err(err.to_string())
-                    }
+                // NOTE: errors (including permission errors) are delivered through `ipfs_callback`
+                let ipfs_call_response = if !ipfs_command.valid_permissions(&static_ctx_ref.data().permissions) {
+                    let err = format!("permission denied: ipfs command `{}`", ipfs_command.name());
+                    CallResult { result: Err(err), attempts: 0 }
+                } else {
+                    ipfs_command.exec_counted(&static_ctx_ref.data().ipfs_client).await
                 };
-                let data = serde_json::to_vec(&ipfs_call_response).expect("failed to serialize module call response");
+                if let Err(err) = &ipfs_call_response.result {
+                    console_error!("Error while running ipfs_command.exec: {}", err);
+                }
+                let data = serde_json::to_vec(&ipfs_call_response.result).expect("failed to serialize module call response");
+                permit.record_bytes_in(data.len() as u64);
                 let result_ptr = utils::encode_data_to_memory(&memory_obj, &alloc_func, &data);
 
-                match ipfs_callback.call2(&JsValue::undefined(), &JsValue::from(result_ptr), &JsValue::from(callback_id)) {
+                // NOTE: `attempts` is an extra argument, guests built before it was added ignore it
+                let attempts = JsValue::from(ipfs_call_response.attempts);
+                match ipfs_callback.call3(&JsValue::undefined(), &JsValue::from(result_ptr), &JsValue::from(callback_id), &attempts) {
                     Ok(_val) => console_log!("ipfs_callback called successfully"),
                     Err(err) => console_error!("Error while running ipfs_callback {}", err.as_string().unwrap_or_default()),
                 };
@@ -473,6 +714,15 @@ impl Blockless {
             let s3_command = serde_json::from_slice::<S3Command>(&buf).expect("failed to deserialize http request");
             console_log!("s3_call: s3_request called: {}", s3_command); // TODO trace
 
+            // NOTE: quotas are enforced before any work is spawned; rejected calls never reach `s3_callback`
+            let permit = match ctx.data().quotas.acquire(Extension::S3, len as u64, time::now_secs()) {
+                Ok(permit) => permit,
+                Err(err) => {
+                    console_error!("s3_call rejected: {}", err);
+                    return QUOTA_EXCEEDED_STATUS;
+                }
+            };
+
             // TODO: we may not need to use async/await here since these are all blocking calls
 
             let boxed_ctx_ref: Box<FunctionEnvMut<Env>> = Box::new(ctx);
@@ -503,15 +753,24 @@ impl Blockless {
                     .clone()
                     .into();
 
-                let s3_call_response = s3_command.exec(&mut static_ctx_ref.data_mut().s3_client).await
-                    .map_err(|err| {
-                        console_error!("Error while running s3_command.exec: {}", err);
-                        err
-                    });
-                let data = serde_json::to_vec(&s3_call_response).expect("failed to serialize module call response");
+                // NOTE: errors (including permission errors) are delivered through `s3_callback`
+                let s3_call_response = if let Err(err) = s3_command.validate() {
+                    CallResult { result: Err(err), attempts: 0 }
+                } else if !s3_command.valid_permissions(&static_ctx_ref.data().permissions) {
+                    CallResult { result: Err(S3Error::PermissionDenied), attempts: 0 }
+                } else {
+                    s3_command.exec_counted(&static_ctx_ref.data().s3_client).await
+                };
+                if let Err(err) = &s3_call_response.result {
+                    console_error!("Error while running s3_command.exec: {}", err);
+                }
+                let data = serde_json::to_vec(&s3_call_response.result).expect("failed to serialize module call response");
+                permit.record_bytes_in(data.len() as u64);
                 let result_ptr = utils::encode_data_to_memory(&memory_obj, &alloc_func, &data);
 
-                match s3_callback.call2(&JsValue::undefined(), &JsValue::from(result_ptr), &JsValue::from(callback_id)) {
+                // NOTE: `attempts` is an extra argument, guests built before it was added ignore it
+                let attempts = JsValue::from(s3_call_response.attempts);
+                match s3_callback.call3(&JsValue::undefined(), &JsValue::from(result_ptr), &JsValue::from(callback_id), &attempts) {
                     Ok(_val) => console_log!("s3_callback called successfully"),
                     Err(err) => console_error!("Error while running s3_callback {}", err.as_string().unwrap_or_default()),
                 };
@@ -528,6 +787,8 @@ impl Blockless {
             "blockless" => {
                 "host_log" => Function::new_typed_with_env(&mut self.store, &env, host_log),
                 "http_call" => Function::new_typed_with_env(&mut self.store, &env, http_call),
+                "http_read_body" => Function::new_typed_with_env(&mut self.store, &env, http_read_body),
+                "http_close" => Function::new_typed_with_env(&mut self.store, &env, http_close),
                 "ipfs_call" => Function::new_typed_with_env(&mut self.store, &env, ipfs_call),
                 "s3_call" => Function::new_typed_with_env(&mut self.store, &env, s3_call),
             },
@@ -653,6 +914,44 @@ impl Blockless {
         }
     }
 
+    /// Get the http response cache statistics and cached urls
+    /// Note: returns `undefined` when the cache is not enabled
+    #[wasm_bindgen(js_name = httpCacheStats)]
+    pub fn http_cache_stats(&self) -> Result<JsValue, JsValue> {
+        match self.http_client.cache() {
+            Some(cache) => utils::to_js_value(&cache.lock().unwrap().stats()),
+            None => Ok(JsValue::undefined()),
+        }
+    }
+
+    /// Get the http exchanges recorded so far (to be saved as replay fixtures)
+    /// Note: returns `undefined` unless the `record` transport is used
+    #[wasm_bindgen(js_name = httpRecording)]
+    pub fn http_recording(&self) -> Result<JsValue, JsValue> {
+        match self.http_client.transport().recorded() {
+            Some(exchanges) => utils::to_js_value(&exchanges),
+            None => Ok(JsValue::undefined()),
+        }
+    }
+
+    /// Remove all responses from the http response cache
+    #[wasm_bindgen(js_name = clearHttpCache)]
+    pub fn clear_http_cache(&self) {
+        if let Some(cache) = self.http_client.cache() {
+            cache.lock().unwrap().clear();
+        }
+    }
+
+    /// Get the quota usage (calls, in-flight calls, bytes in/out and rejected calls) per extension
+    #[wasm_bindgen(js_name = quotaUsage)]
+    pub fn quota_usage(&self) -> Result<JsValue, JsValue> {
+        let usage: HashMap<Extension, QuotaUsage> = [Extension::Http, Extension::S3, Extension::Ipfs]
+            .into_iter()
+            .map(|extension| (extension, self.quotas.usage(extension)))
+            .collect();
+        utils::to_js_value(&usage)
+    }
+
     #[wasm_bindgen(js_name = getInstance)]
     pub fn instance(&self) -> Result<js_sys::WebAssembly::Instance, JsValue> {
         let instance = self.instance.as_ref().ok_or(js_sys::Error::new("Instance not set"))?;
@@ -726,3 +1025,10 @@ impl Blockless {
         self.set_stdin_buffer(input.as_bytes())
     }
 }
+
+impl Drop for Blockless {
+    fn drop(&mut self) {
+        // release response bodies the guest did not close
+        streams::close_all(self.id);
+    }
+}
@@ -0,0 +1,52 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use bls_common::http::{HttpBodyStream, HttpError};
+
+static NEXT_HANDLE: AtomicU32 = AtomicU32::new(1);
+
+// NOTE: response bodies wrap js objects (not `Send`), so they cannot live in the wasmer `FunctionEnv`;
+// they are kept per runtime instance instead - keyed by `(instance_id, handle)`.
+// A `None` value marks a stream that is currently being read.
+thread_local! {
+    static HTTP_STREAMS: RefCell<HashMap<(u32, u32), Option<HttpBodyStream>>> = RefCell::new(HashMap::new());
+}
+
+/// Registers an open response body and returns its handle.
+pub fn insert(instance_id: u32, stream: HttpBodyStream) -> u32 {
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::SeqCst);
+    HTTP_STREAMS.with(|streams| streams.borrow_mut().insert((instance_id, handle), Some(stream)));
+    handle
+}
+
+/// Takes the stream out of the table for reading; it must be handed back with `restore`.
+pub fn take(instance_id: u32, handle: u32) -> Result<HttpBodyStream, HttpError> {
+    HTTP_STREAMS.with(|streams| {
+        match streams.borrow_mut().get_mut(&(instance_id, handle)) {
+            Some(stream) => stream
+                .take()
+                .ok_or_else(|| HttpError::InvalidRequest("body is already being read".into())),
+            None => Err(HttpError::InvalidRequest("unknown body handle".into())),
+        }
+    })
+}
+
+/// Hands back a stream taken with `take`; dropped if the handle was closed in the meantime.
+pub fn restore(instance_id: u32, handle: u32, stream: HttpBodyStream) {
+    HTTP_STREAMS.with(|streams| {
+        if let Some(slot) = streams.borrow_mut().get_mut(&(instance_id, handle)) {
+            slot.replace(stream);
+        }
+    });
+}
+
+/// Closes the stream; returns false if the handle is unknown.
+pub fn close(instance_id: u32, handle: u32) -> bool {
+    HTTP_STREAMS.with(|streams| streams.borrow_mut().remove(&(instance_id, handle)).is_some())
+}
+
+/// Closes every stream opened by the runtime instance.
+pub fn close_all(instance_id: u32) {
+    HTTP_STREAMS.with(|streams| streams.borrow_mut().retain(|(id, _), _| *id != instance_id));
+}
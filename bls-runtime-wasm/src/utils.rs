@@ -31,4 +31,25 @@ pub fn encode_data_to_memory(memory: &WebAssembly::Memory, alloc_func: &Function
     mem_array.set(&Uint8Array::from(data), result_ptr + 4);
 
     result_ptr
-}
\ No newline at end of file
+}
+
+/// Reads an optional section of the `BlocklessConfig` object and deserializes it (via JSON).
+/// Returns the default value when the section is not set.
+pub fn get_config_section<T: serde::de::DeserializeOwned + Default>(config: &JsValue, key: &str) -> Result<T, JsValue> {
+    let section = Reflect::get(config, &key.into())?;
+    if section.is_undefined() || section.is_null() {
+        return Ok(T::default());
+    }
+    let json = js_sys::JSON::stringify(&section)?
+        .as_string()
+        .ok_or_else(|| js_sys::Error::new(&format!("Invalid `{}` config", key)))?;
+    serde_json::from_str(&json)
+        .map_err(|e| js_sys::Error::new(&format!("Invalid `{}` config: {}", key, e)).into())
+}
+
+/// Converts a serializable value into a plain js object (via JSON).
+pub fn to_js_value<T: serde::Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    let json = serde_json::to_string(value)
+        .map_err(|e| js_sys::Error::new(&format!("Failed to serialize value: {}", e)))?;
+    js_sys::JSON::parse(&json)
+}
@@ -1,7 +1,20 @@
 
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use serde::{Deserialize, Serialize};
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeMap;
 use crate::impl_display;
+use crate::permissions::Permissions;
+use crate::quota::Quotas;
+use crate::retry::RetryPolicy;
+#[cfg(feature = "use-wasm-bindgen")]
+use crate::time::{now_secs, with_timeout};
+use self::cache::{CacheLookup, HttpCache, HttpCacheConfig};
+
+pub mod cache;
+#[cfg(feature = "use-wasm-bindgen")]
+pub mod transport;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum Method {
@@ -11,25 +24,51 @@ pub enum Method {
   Delete,
   Head,
   Trace,
+  Patch,
+  Options,
+  Connect,
+  /// extension method (e.g. `PURGE`)
+  Other(String),
 }
 
 impl Default for Method {
   fn default() -> Self { Method::Get }
 }
 
+impl Method {
+  /// Idempotent methods per RFC 9110; only these are retried unless the retry policy says otherwise.
+  pub fn is_idempotent(&self) -> bool {
+    matches!(self, Method::Get | Method::Head | Method::Put | Method::Delete | Method::Options | Method::Trace)
+  }
+}
+
 impl AsRef<[u8]> for Method {
   fn as_ref(&self) -> &[u8] {
+    // NOTE: method names are case-sensitive on the wire; fetch only normalizes some of them (not PATCH)
     match self {
-      Method::Get => b"get",
-      Method::Post => b"post",
-      Method::Put => b"put",
-      Method::Delete => b"delete",
-      Method::Head => b"head",
-      Method::Trace => b"trace",
+      Method::Get => b"GET",
+      Method::Post => b"POST",
+      Method::Put => b"PUT",
+      Method::Delete => b"DELETE",
+      Method::Head => b"HEAD",
+      Method::Trace => b"TRACE",
+      Method::Patch => b"PATCH",
+      Method::Options => b"OPTIONS",
+      Method::Connect => b"CONNECT",
+      Method::Other(method) => method.as_bytes(),
     }
   }
 }
 
+// NOTE: compare by name so that e.g. `Other("GET")` cannot sidestep a `GET` permission rule
+impl PartialEq for Method {
+  fn eq(&self, other: &Self) -> bool {
+    self.as_ref().eq_ignore_ascii_case(other.as_ref())
+  }
+}
+
+impl Eq for Method {}
+
 // allow to parse from string
 impl std::str::FromStr for Method {
   type Err = &'static str;
@@ -41,18 +80,182 @@ impl std::str::FromStr for Method {
       "delete" => Ok(Method::Delete),
       "head" => Ok(Method::Head),
       "trace" => Ok(Method::Trace),
+      "patch" => Ok(Method::Patch),
+      "options" => Ok(Method::Options),
+      "connect" => Ok(Method::Connect),
+      _ if is_token(s) => Ok(Method::Other(s.into())),
       _ => Err("Invalid method name"),
     }
   }
 }
 
+/// https://www.rfc-editor.org/rfc/rfc9110#name-tokens
+fn is_token(s: &str) -> bool {
+  !s.is_empty() && s.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Ordered http headers that may hold several values per name; lookups are case-insensitive.
+///
+/// Serialized as a json object in header order - repeated names are written as repeated keys,
+/// so guests decoding into a `HashMap<String, String>` still work (the last value wins).
+/// Deserializes from such an object or from a list of `[name, value]` pairs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpHeaders(Vec<(String, String)>);
+
+impl HttpHeaders {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// First value of the header.
+  pub fn get(&self, name: &str) -> Option<&str> {
+    self.0
+      .iter()
+      .find(|(key, _)| key.eq_ignore_ascii_case(name))
+      .map(|(_, value)| value.as_str())
+  }
+
+  /// All values of the header, in order.
+  pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    self.0
+      .iter()
+      .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+      .map(|(_, value)| value.as_str())
+  }
+
+  pub fn contains(&self, name: &str) -> bool {
+    self.get(name).is_some()
+  }
+
+  /// Adds a value, keeping existing values of the header.
+  pub fn append(&mut self, name: &str, value: &str) {
+    self.0.push((name.into(), value.into()));
+  }
+
+  /// Sets the value, replacing all existing values of the header.
+  pub fn insert(&mut self, name: &str, value: &str) {
+    self.remove(name);
+    self.append(name, value);
+  }
+
+  pub fn remove(&mut self, name: &str) {
+    self.0.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+    self.0.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+impl FromIterator<(String, String)> for HttpHeaders {
+  fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+    HttpHeaders(iter.into_iter().collect())
+  }
+}
+
+impl From<HashMap<String, String>> for HttpHeaders {
+  fn from(headers: HashMap<String, String>) -> Self {
+    headers.into_iter().collect()
+  }
+}
+
+impl Serialize for HttpHeaders {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(self.0.len()))?;
+    for (key, value) in &self.0 {
+      map.serialize_entry(key, value)?;
+    }
+    map.end()
+  }
+}
+
+impl<'de> Deserialize<'de> for HttpHeaders {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct HttpHeadersVisitor;
+
+    impl<'de> Visitor<'de> for HttpHeadersVisitor {
+      type Value = HttpHeaders;
+
+      fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a map of headers or a list of [name, value] pairs")
+      }
+
+      fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut headers = HttpHeaders::new();
+        while let Some((key, value)) = map.next_entry::<String, String>()? {
+          headers.0.push((key, value));
+        }
+        Ok(headers)
+      }
+
+      fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut headers = HttpHeaders::new();
+        while let Some(pair) = seq.next_element::<(String, String)>()? {
+          headers.0.push(pair);
+        }
+        Ok(headers)
+      }
+    }
+
+    deserializer.deserialize_any(HttpHeadersVisitor)
+  }
+}
+
+#[cfg(feature = "use-wasm-bindgen")]
+impl TryFrom<&HttpHeaders> for reqwest::header::HeaderMap {
+  type Error = &'static str;
+
+  fn try_from(headers: &HttpHeaders) -> Result<Self, Self::Error> {
+    let mut header_map = reqwest::header::HeaderMap::new();
+    for (key, value) in headers.iter() {
+      let name = reqwest::header::HeaderName::from_bytes(key.as_bytes()).map_err(|_| "invalid header name")?;
+      let value = reqwest::header::HeaderValue::from_str(value).map_err(|_| "invalid header value")?;
+      header_map.append(name, value);
+    }
+    Ok(header_map)
+  }
+}
+
+#[cfg(feature = "use-wasm-bindgen")]
+impl From<&reqwest::header::HeaderMap> for HttpHeaders {
+  fn from(header_map: &reqwest::header::HeaderMap) -> Self {
+    header_map
+      .iter()
+      .map(|(key, value)| {
+        // NOTE: values that are not valid utf-8 are decoded as latin-1 (instead of being dropped)
+        let value = match value.to_str() {
+          Ok(value) => value.to_string(),
+          Err(_) => value.as_bytes().iter().map(|&b| b as char).collect(),
+        };
+        (key.to_string(), value)
+      })
+      .collect()
+  }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct HttpRequest {
   pub url: String,
   pub method: Method,
-  pub headers: Option<HashMap<String, String>>,
-  pub body: Option<String>,
-  // pub timeout: Option<u64>, // NOTE: timeout does not work in wasm
+  pub headers: Option<HttpHeaders>,
+  #[serde(default, deserialize_with = "deserialize_body")]
+  pub body: Option<HttpBody>,
+  /// request timeout in milliseconds; overrides `HttpConfig::timeout`
+  pub timeout: Option<u64>,
+  /// when set, the response body is not buffered; the guest reads it through `http_read_body`
+  #[serde(default)]
+  pub stream: bool,
+  /// overrides the runtime retry policy (`BlocklessConfig.retry`), within its attempts and backoff
+  #[serde(default)]
+  pub retry: Option<RetryPolicy>,
 }
 
 impl HttpRequest {
@@ -64,23 +267,90 @@ impl HttpRequest {
     }
   }
 
-  pub fn valid_permissions(&self, permissions: &[String]) -> bool {
-    // return true if url (to lower) starts with permissions (lower case)
-    let url = self.url.to_ascii_lowercase();
-    permissions
-      .iter()
-      .any(|p| url.starts_with(&p.to_lowercase()))
+  pub fn with_header(mut self, name: &str, value: &str) -> Self {
+    self.headers
+      .get_or_insert_with(HttpHeaders::new)
+      .append(name, value);
+    self
+  }
+
+  pub fn with_body(mut self, body: HttpBody) -> Self {
+    self.body = Some(body);
+    self
+  }
+
+  pub fn with_bytes(self, bytes: Vec<u8>) -> Self {
+    self.with_body(HttpBody::Bytes(bytes))
+  }
+
+  pub fn with_text(self, text: &str) -> Self {
+    self.with_body(HttpBody::Text(text.into()))
+  }
+
+  pub fn with_json(self, value: &impl Serialize) -> Result<Self, serde_json::Error> {
+    Ok(self.with_body(HttpBody::Json(serde_json::to_value(value)?)))
+  }
+
+  pub fn with_form(self, fields: &[(&str, &str)]) -> Self {
+    let fields = fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    self.with_body(HttpBody::Form(fields))
+  }
+
+  pub fn with_multipart(self, parts: Vec<MultipartPart>) -> Self {
+    self.with_body(HttpBody::Multipart(parts))
+  }
+
+  pub fn with_stream(mut self) -> Self {
+    self.stream = true;
+    self
+  }
+
+  pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+    self.retry = Some(retry);
+    self
+  }
+
+  pub fn valid_permissions(&self, permissions: &Permissions) -> bool {
+    permissions.allows_http(&self.method, &self.url)
   }
 
   #[cfg(feature = "use-wasm-bindgen")]
-  pub async fn request(&self) -> Result<reqwest::Response, &'static str> {
-    let request: reqwest::Request = self.to_owned().try_into()?;
-    let resp = reqwest::Client::new().execute(request).await.map_err(|_e| {
-      // error!("request send error, {}", _e);
-      "request send error"
-    })?;
+  pub async fn request(&self) -> Result<reqwest::Response, HttpError> {
+    let request: reqwest::Request = self.to_owned()
+      .try_into()
+      .map_err(|e: &str| HttpError::InvalidRequest(e.into()))?;
+    let resp = reqwest::Client::new()
+      .execute(request)
+      .await
+      .map_err(|e| HttpError::Request(e.to_string()))?;
     Ok(resp)
   }
+
+  /// Performs the request and reads the full response body.
+  /// The timeout (if any) covers both sending the request and reading the body.
+  #[cfg(feature = "use-wasm-bindgen")]
+  pub async fn fetch(&self, config: &HttpConfig) -> Result<HttpResponse, HttpError> {
+    let response = async {
+      let response = self.request().await?;
+      HttpResponse::from_reqwest(response).await
+    };
+    match self.timeout.or(config.timeout) {
+      Some(timeout) => with_timeout(response, timeout, HttpError::Timeout).await,
+      None => response.await,
+    }
+  }
+
+  /// Performs the request without reading the response body.
+  /// The timeout (if any) applies to receiving the response head and to every body read.
+  #[cfg(feature = "use-wasm-bindgen")]
+  pub async fn fetch_stream(&self, config: &HttpConfig) -> Result<(HttpResponse, HttpBodyStream), HttpError> {
+    let timeout = self.timeout.or(config.timeout);
+    let response = match timeout {
+      Some(timeout) => with_timeout(self.request(), timeout, HttpError::Timeout).await?,
+      None => self.request().await?,
+    };
+    Ok((HttpResponse::head_from_reqwest(&response), HttpBodyStream::new(response, timeout)))
+  }
 }
 
 #[cfg(feature = "use-wasm-bindgen")]
@@ -92,50 +362,698 @@ impl TryInto<reqwest::Request> for HttpRequest {
     let method = reqwest::Method::from_bytes(self.method.as_ref())
       .map_err(|_| "invalid method")?;
 
-    let mut request = reqwest::Request::new(method, url);
+    // NOTE: use the request builder since it knows how to encode json, form and multipart bodies
+    let mut builder = reqwest::Client::new().request(method, url);
 
     if let Some(headers) = &self.headers {
-      let header_map: reqwest::header::HeaderMap = headers.try_into().expect("valid headers");
-      request.headers_mut().extend(header_map.into_iter());
+      let header_map = reqwest::header::HeaderMap::try_from(headers)?;
+      builder = builder.headers(header_map);
+    };
+    builder = match self.body {
+      Some(HttpBody::Bytes(bytes)) => builder.body(bytes),
+      Some(HttpBody::Text(text)) => builder.body(text),
+      Some(HttpBody::Json(value)) => builder.json(&value),
+      Some(HttpBody::Form(fields)) => builder.form(&fields),
+      Some(HttpBody::Multipart(parts)) => {
+        let mut form = reqwest::multipart::Form::new();
+        for part in parts {
+          form = form.part(part.name.clone(), part.try_into()?);
+        }
+        builder.multipart(form)
+      }
+      None => builder,
     };
-    if let Some(body) = self.body {
-      request.body_mut().replace(body.into());
+
+    builder.build().map_err(|_| "invalid request")
+  }
+}
+
+impl_display!(HttpRequest);
+
+/// Request body sent by the guest.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum HttpBody {
+  /// raw bytes, sent as-is
+  Bytes(Vec<u8>),
+  /// utf-8 text, sent as-is
+  Text(String),
+  /// json value; sets `Content-Type: application/json` unless set in the headers
+  Json(serde_json::Value),
+  /// `application/x-www-form-urlencoded` fields
+  Form(Vec<(String, String)>),
+  /// `multipart/form-data` parts
+  Multipart(Vec<MultipartPart>),
+}
+
+/// Accepts both the tagged `HttpBody` and a plain string (the body format used by older guests).
+fn deserialize_body<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<HttpBody>, D::Error> {
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum BodyCompat {
+    Text(String),
+    Body(HttpBody),
+  }
+  Ok(Option::<BodyCompat>::deserialize(deserializer)?.map(|body| match body {
+    BodyCompat::Text(text) => HttpBody::Text(text),
+    BodyCompat::Body(body) => body,
+  }))
+}
+
+/// A single part of a multipart body; parts with a `file_name` are sent as file parts.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct MultipartPart {
+  pub name: String,
+  pub file_name: Option<String>,
+  pub content_type: Option<String>,
+  pub data: Vec<u8>,
+}
+
+impl MultipartPart {
+  pub fn text(name: &str, value: &str) -> Self {
+    MultipartPart {
+      name: name.into(),
+      data: value.as_bytes().to_vec(),
+      ..Default::default()
+    }
+  }
+
+  pub fn file(name: &str, file_name: &str, data: Vec<u8>) -> Self {
+    MultipartPart {
+      name: name.into(),
+      file_name: Some(file_name.into()),
+      data,
+      ..Default::default()
     }
+  }
+
+  pub fn with_content_type(mut self, content_type: &str) -> Self {
+    self.content_type = Some(content_type.into());
+    self
+  }
+}
 
-    // NOTE: timeout does not work in wasm
-    // if let Some(timeout) = self.timeout {
-    //   request.timeout_mut().replace(core::time::Duration::from_secs(timeout));
-    // }
+#[cfg(feature = "use-wasm-bindgen")]
+impl TryInto<reqwest::multipart::Part> for MultipartPart {
+  type Error = &'static str;
 
-    Ok(request)
+  fn try_into(self) -> Result<reqwest::multipart::Part, Self::Error> {
+    let mut part = reqwest::multipart::Part::bytes(self.data);
+    if let Some(file_name) = self.file_name {
+      part = part.file_name(file_name);
+    }
+    if let Some(content_type) = self.content_type {
+      part = part.mime_str(&content_type).map_err(|_| "invalid multipart content type")?;
+    }
+    Ok(part)
   }
 }
 
-impl_display!(HttpRequest);
+/// Runtime-wide http settings (`BlocklessConfig.http`).
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpConfig {
+  /// default request timeout in milliseconds
+  pub timeout: Option<u64>,
+  /// response cache; disabled when not set
+  pub cache: Option<HttpCacheConfig>,
+  /// how requests are sent (live, mocked, recorded or replayed)
+  #[cfg(feature = "use-wasm-bindgen")]
+  #[serde(default)]
+  pub transport: transport::HttpTransportConfig,
+}
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Performs guest http requests with the runtime-wide settings; clones share the response cache and transport.
+#[derive(Debug, Clone, Default)]
+pub struct HttpClient {
+  config: HttpConfig,
+  cache: Option<Arc<Mutex<HttpCache>>>,
+  retry: RetryPolicy,
+  quotas: Option<Quotas>,
+  #[cfg(feature = "use-wasm-bindgen")]
+  transport: Option<Arc<dyn transport::HttpTransport>>,
+}
+
+impl HttpClient {
+  pub fn new(config: HttpConfig) -> Self {
+    let cache = config.cache.as_ref().map(|cache| Arc::new(Mutex::new(HttpCache::new(cache))));
+    HttpClient {
+      #[cfg(feature = "use-wasm-bindgen")]
+      transport: Some(config.transport.build()),
+      config,
+      cache,
+      retry: RetryPolicy::default(),
+      quotas: None,
+    }
+  }
+
+  /// Sets the retry policy used for requests that do not carry their own; it also caps the ones they carry.
+  pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+    self.retry = retry;
+    self
+  }
+
+  /// Charges every retry against the `http` quota, like a call of its own.
+  pub fn with_quotas(mut self, quotas: Quotas) -> Self {
+    self.quotas = Some(quotas);
+    self
+  }
+
+  pub fn config(&self) -> &HttpConfig {
+    &self.config
+  }
+
+  pub fn retry(&self) -> &RetryPolicy {
+    &self.retry
+  }
+
+  /// Policy of the request, capped at the runtime policy.
+  pub fn retry_policy(&self, request: &HttpRequest) -> RetryPolicy {
+    match &request.retry {
+      Some(retry) => retry.limited_to(&self.retry),
+      None => self.retry.clone(),
+    }
+  }
+
+  #[cfg(feature = "use-wasm-bindgen")]
+  pub fn transport(&self) -> &dyn transport::HttpTransport {
+    match &self.transport {
+      Some(transport) => transport.as_ref(),
+      None => &transport::LiveTransport,
+    }
+  }
+
+  pub fn cache(&self) -> Option<&Arc<Mutex<HttpCache>>> {
+    self.cache.as_ref()
+  }
+
+  /// Performs the request, serving (and storing) cacheable `GET` requests from the cache when enabled.
+  #[cfg(feature = "use-wasm-bindgen")]
+  pub async fn fetch(&self, request: &HttpRequest) -> Result<HttpResponse, HttpError> {
+    let cache = match &self.cache {
+      Some(cache) if HttpCache::is_cacheable(request) => cache,
+      _ => return self.send(request).await,
+    };
+
+    let lookup = cache.lock().unwrap().lookup(request, now_secs() as u64);
+    let response = match lookup {
+      CacheLookup::Fresh(response) => return Ok(HttpResponse { attempts: 0, ..response }),
+      CacheLookup::Stale(conditional) => {
+        let mut conditional_request = request.clone();
+        for (name, value) in conditional.iter() {
+          conditional_request = conditional_request.with_header(name, value);
+        }
+        let response = self.send(&conditional_request).await?;
+        if response.status == 304 {
+          let revalidated = cache.lock().unwrap().revalidate(request, &response, now_secs() as u64);
+          if let Some(revalidated) = revalidated {
+            return Ok(HttpResponse { attempts: response.attempts, ..revalidated });
+          }
+          // the entry was evicted meanwhile; the guest did not ask for a conditional request
+          let unconditional = self.send(request).await?;
+          HttpResponse { attempts: response.attempts + unconditional.attempts, ..unconditional }
+        } else {
+          response
+        }
+      }
+      CacheLookup::Miss => self.send(request).await?,
+    };
+    cache.lock().unwrap().store(request, &response, now_secs() as u64);
+    Ok(response)
+  }
+
+  /// Like `fetch` but leaves the body on the network; only receiving the response head is retried.
+  #[cfg(feature = "use-wasm-bindgen")]
+  pub async fn fetch_stream(&self, request: &HttpRequest) -> Result<(HttpResponse, HttpBodyStream), HttpError> {
+    let policy = &self.retry_policy(request);
+    let (result, attempts) = policy
+      .run_charged(
+        request.method.is_idempotent(),
+        || self.transport().fetch_stream(request, &self.config),
+        |result| is_retryable(policy, result.as_ref().map(|(response, _)| response)),
+        || self.charge_retry(),
+      )
+      .await;
+    result.map(|(response, body)| (HttpResponse { attempts, ..response }, body))
+  }
+
+  /// Sends the request through the transport, retrying according to the request (or runtime) retry policy.
+  #[cfg(feature = "use-wasm-bindgen")]
+  async fn send(&self, request: &HttpRequest) -> Result<HttpResponse, HttpError> {
+    let policy = &self.retry_policy(request);
+    let (result, attempts) = policy
+      .run_charged(
+        request.method.is_idempotent(),
+        || self.transport().fetch(request, &self.config),
+        |result| is_retryable(policy, result.as_ref()),
+        || self.charge_retry(),
+      )
+      .await;
+    result.map(|response| HttpResponse { attempts, ..response })
+  }
+
+  #[cfg(feature = "use-wasm-bindgen")]
+  fn charge_retry(&self) -> bool {
+    match &self.quotas {
+      Some(quotas) => quotas.charge_retry(crate::quota::Extension::Http, now_secs()).is_ok(),
+      None => true,
+    }
+  }
+}
+
+#[cfg(feature = "use-wasm-bindgen")]
+/// Failed sends, timeouts and the policy's status codes are retried; permission and request errors are not.
+fn is_retryable(policy: &RetryPolicy, result: Result<&HttpResponse, &HttpError>) -> bool {
+  match result {
+    Ok(response) => policy.is_retryable_status(response.status),
+    Err(HttpError::Timeout(_) | HttpError::Request(_) | HttpError::Response(_)) => true,
+    Err(_) => false,
+  }
+}
+
+/// Error returned to the guest through `http_callback`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum HttpError {
+  /// the request is not allowed by the runtime permissions
+  PermissionDenied,
+  /// the request could not be built (e.g. invalid url or headers)
+  InvalidRequest(String),
+  /// no response was received within the timeout (in milliseconds)
+  Timeout(u64),
+  /// the request failed to send
+  Request(String),
+  /// the response body could not be read
+  Response(String),
+  /// the call could not be dispatched to/from the host runtime
+  Dispatch(String),
+  /// the call was rejected by the runtime quotas (`BlocklessConfig.quotas`)
+  QuotaExceeded,
+}
+
+impl std::fmt::Display for HttpError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      HttpError::PermissionDenied => write!(f, "permission denied"),
+      HttpError::InvalidRequest(e) => write!(f, "invalid request: {}", e),
+      HttpError::Timeout(timeout) => write!(f, "request timed out after {}ms", timeout),
+      HttpError::Request(e) => write!(f, "request send error: {}", e),
+      HttpError::Response(e) => write!(f, "response body error: {}", e),
+      HttpError::Dispatch(e) => write!(f, "dispatch error: {}", e),
+      HttpError::QuotaExceeded => write!(f, "quota exceeded"),
+    }
+  }
+}
+
+impl From<&'static str> for HttpError {
+  fn from(e: &'static str) -> Self {
+    HttpError::Dispatch(e.into())
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct HttpResponse {
   pub status: u16,
-  pub headers: HashMap<String, String>,
+  pub headers: HttpHeaders,
   pub body: Vec<u8>,
+  /// handle of the streamed body (see `HttpRequest::stream`); `body` is empty when set
+  #[serde(default)]
+  pub body_handle: Option<u32>,
+  /// number of network attempts made (see `RetryPolicy`); 0 when served from the cache
+  #[serde(default)]
+  pub attempts: u32,
 }
 
 impl HttpResponse {
   // NOTE: cannot use `impl TryFrom<reqwest::Response> for HttpResponse` because reading body is async
   #[cfg(feature = "use-wasm-bindgen")]
-  pub async fn from_reqwest(resp: reqwest::Response) -> Result<Self, String> {
-    let mut headers = HashMap::new();
-    for (key, value) in resp.headers().iter() {
-      headers.insert(key.to_string(), value.to_str().unwrap_or_default().to_string());
-    }
-    let status = resp.status().as_u16();
-    let body = resp.bytes().await.map_err(|_| "response body error")?;
+  pub async fn from_reqwest(resp: reqwest::Response) -> Result<Self, HttpError> {
+    let head = Self::head_from_reqwest(&resp);
+    let body = resp.bytes().await.map_err(|e| HttpError::Response(e.to_string()))?;
     Ok(HttpResponse {
-      status,
-      headers,
       body: body.to_vec(),
+      ..head
     })
   }
+
+  /// Status and headers of the response, without the body.
+  #[cfg(feature = "use-wasm-bindgen")]
+  pub fn head_from_reqwest(resp: &reqwest::Response) -> Self {
+    HttpResponse {
+      status: resp.status().as_u16(),
+      headers: resp.headers().into(),
+      body: vec![],
+      body_handle: None,
+      attempts: 1,
+    }
+  }
 }
 
 impl_display!(HttpResponse);
+
+/// Body of a streamed response; chunks are only pulled from the network when the guest reads them.
+#[cfg(feature = "use-wasm-bindgen")]
+pub struct HttpBodyStream {
+  chunks: std::pin::Pin<Box<dyn futures::Stream<Item = Result<Vec<u8>, HttpError>>>>,
+  pending: Vec<u8>,
+  timeout: Option<u64>,
+}
+
+#[cfg(feature = "use-wasm-bindgen")]
+impl HttpBodyStream {
+  pub fn new(response: reqwest::Response, timeout: Option<u64>) -> Self {
+    use futures::StreamExt;
+
+    let chunks = response
+      .bytes_stream()
+      .map(|chunk| chunk.map(|bytes| bytes.to_vec()).map_err(|e| HttpError::Response(e.to_string())));
+    HttpBodyStream {
+      chunks: Box::pin(chunks),
+      pending: vec![],
+      timeout,
+    }
+  }
+
+  /// Streams a body that is already in memory.
+  pub fn from_bytes(body: Vec<u8>) -> Self {
+    let chunks = futures::stream::iter((!body.is_empty()).then_some(Ok(body)));
+    HttpBodyStream {
+      chunks: Box::pin(chunks),
+      pending: vec![],
+      timeout: None,
+    }
+  }
+
+  /// Reads up to `max_len` bytes of the body; an empty result means the body has been fully read.
+  pub async fn read(&mut self, max_len: usize) -> Result<Vec<u8>, HttpError> {
+    use futures::StreamExt;
+
+    if max_len == 0 {
+      return Err(HttpError::InvalidRequest("max_len must be greater than 0".into()));
+    }
+    while self.pending.is_empty() {
+      let chunks = &mut self.chunks;
+      let next = match self.timeout {
+        Some(timeout) => with_timeout(async { Ok(chunks.next().await) }, timeout, HttpError::Timeout).await?,
+        None => chunks.next().await,
+      };
+      match next {
+        Some(chunk) => self.pending = chunk?,
+        None => return Ok(vec![]),
+      }
+    }
+    let len = max_len.min(self.pending.len());
+    Ok(self.pending.drain(..len).collect())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_headers() {
+    let mut headers = HttpHeaders::new();
+    headers.append("Set-Cookie", "a=1");
+    headers.append("Vary", "Accept");
+    headers.append("set-cookie", "b=2");
+    assert_eq!(headers.get("SET-COOKIE"), Some("a=1"));
+    assert_eq!(headers.get_all("set-cookie").collect::<Vec<_>>(), vec!["a=1", "b=2"]);
+    assert!(headers.contains("vary"));
+
+    // repeated names are kept in order and stay readable as a plain map
+    let json = serde_json::to_string(&headers).unwrap();
+    assert_eq!(json, r#"{"Set-Cookie":"a=1","Vary":"Accept","set-cookie":"b=2"}"#);
+    assert_eq!(serde_json::from_str::<HttpHeaders>(&json).unwrap(), headers);
+    let legacy: HashMap<String, String> = serde_json::from_str(&json).unwrap();
+    assert_eq!(legacy["Vary"], "Accept");
+    let pairs: HttpHeaders = serde_json::from_str(r#"[["a","1"],["A","2"]]"#).unwrap();
+    assert_eq!(pairs.get_all("a").count(), 2);
+
+    headers.insert("set-cookie", "c=3");
+    assert_eq!(headers.get_all("Set-Cookie").collect::<Vec<_>>(), vec!["c=3"]);
+    headers.remove("VARY");
+    assert_eq!(headers.len(), 1);
+  }
+
+  #[test]
+  fn test_headers_reqwest_conversion() {
+    let mut header_map = reqwest::header::HeaderMap::new();
+    header_map.append("set-cookie", "a=1".parse().unwrap());
+    header_map.append("set-cookie", "b=2".parse().unwrap());
+    header_map.append("x-latin", reqwest::header::HeaderValue::from_bytes(b"caf\xe9").unwrap());
+    let headers = HttpHeaders::from(&header_map);
+    assert_eq!(headers.get_all("set-cookie").collect::<Vec<_>>(), vec!["a=1", "b=2"]);
+    assert_eq!(headers.get("x-latin"), Some("caf\u{e9}"));
+
+    let request: reqwest::Request = HttpRequest::new("http://a.b", Method::Get)
+      .with_header("Accept", "text/html")
+      .with_header("accept", "application/json")
+      .try_into()
+      .unwrap();
+    assert_eq!(request.headers().get_all("accept").iter().count(), 2);
+  }
+
+  #[test]
+  fn test_method_parse() {
+    assert_eq!("patch".parse::<Method>().unwrap(), Method::Patch);
+    assert_eq!("OPTIONS".parse::<Method>().unwrap(), Method::Options);
+    assert_eq!("PURGE".parse::<Method>().unwrap(), Method::Other("PURGE".into()));
+    assert_eq!(Method::Other("get".into()), Method::Get);
+    assert!("GE T".parse::<Method>().is_err());
+    assert!("".parse::<Method>().is_err());
+
+    let method: reqwest::Method = reqwest::Method::from_bytes(Method::Patch.as_ref()).unwrap();
+    assert_eq!(method, reqwest::Method::PATCH);
+    let method: reqwest::Method = reqwest::Method::from_bytes(Method::Other("PURGE".into()).as_ref()).unwrap();
+    assert_eq!(method.as_str(), "PURGE");
+
+    let json = serde_json::to_string(&vec![Method::Get, Method::Other("PURGE".into())]).unwrap();
+    assert_eq!(json, r#"["Get",{"Other":"PURGE"}]"#);
+  }
+
+  #[test]
+  fn test_body_serde_compat() {
+    let request: HttpRequest = serde_json::from_str(r#"{"url":"http://a.b","method":"Post","headers":null,"body":"hello"}"#).unwrap();
+    assert_eq!(request.body, Some(HttpBody::Text("hello".into())));
+    assert_eq!(request.timeout, None);
+
+    let request: HttpRequest = serde_json::from_str(r#"{"url":"http://a.b","method":"Get","headers":null}"#).unwrap();
+    assert_eq!(request.body, None);
+
+    let request = HttpRequest::new("http://a.b", Method::Post).with_multipart(vec![
+      MultipartPart::text("name", "value"),
+      MultipartPart::file("file", "a.png", vec![0, 159, 146]).with_content_type("image/png"),
+    ]);
+    let roundtrip: HttpRequest = serde_json::from_str(&request.to_string()).unwrap();
+    assert_eq!(roundtrip.body, request.body);
+  }
+
+  #[test]
+  fn test_body_into_reqwest() {
+    let into_request = |request: HttpRequest| -> reqwest::Request { request.try_into().unwrap() };
+    let body_bytes = |request: &reqwest::Request| request.body().and_then(|b| b.as_bytes()).unwrap().to_vec();
+
+    let request = into_request(HttpRequest::new("http://a.b", Method::Post).with_bytes(vec![0, 255, 1]));
+    assert_eq!(body_bytes(&request), vec![0, 255, 1]);
+
+    let request = into_request(HttpRequest::new("http://a.b", Method::Post).with_json(&vec![1, 2]).unwrap());
+    assert_eq!(request.headers()["content-type"], "application/json");
+    assert_eq!(body_bytes(&request), b"[1,2]");
+
+    let request = into_request(HttpRequest::new("http://a.b", Method::Post).with_form(&[("a", "1"), ("b", "x y")]));
+    assert_eq!(request.headers()["content-type"], "application/x-www-form-urlencoded");
+    assert_eq!(body_bytes(&request), b"a=1&b=x+y");
+
+    let request = into_request(HttpRequest::new("http://a.b", Method::Post).with_multipart(vec![
+      MultipartPart::file("file", "a.bin", vec![1, 2, 3]),
+    ]));
+    let content_type = request.headers()["content-type"].to_str().unwrap();
+    assert!(content_type.starts_with("multipart/form-data; boundary="));
+  }
+
+  #[tokio::test]
+  async fn test_fetch_stream() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      let (mut socket, _) = listener.accept().await.unwrap();
+      let mut buf = [0u8; 1024];
+      let _ = socket.read(&mut buf).await.unwrap();
+      socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123456789").await.unwrap();
+    });
+
+    let request = HttpRequest::new(&format!("http://{}/", addr), Method::Get).with_stream();
+    let (response, mut body) = request.fetch_stream(&HttpConfig::default()).await.unwrap();
+    assert_eq!(response.status, 200);
+    assert!(response.body.is_empty());
+
+    assert_eq!(body.read(4).await.unwrap(), b"0123");
+    assert_eq!(body.read(4).await.unwrap(), b"4567");
+    assert_eq!(body.read(4).await.unwrap(), b"89");
+    assert_eq!(body.read(4).await.unwrap(), b"");
+    assert!(body.read(0).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn test_client_cache() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // first response is cacheable but must be revalidated, the revalidation answers `304 Not Modified`
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+      let mut requests = vec![];
+      for response in [
+        "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        "HTTP/1.1 304 Not Modified\r\nCache-Control: max-age=60\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n",
+      ] {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 1024];
+        let len = socket.read(&mut buf).await.unwrap();
+        requests.push(String::from_utf8_lossy(&buf[..len]).to_string());
+        socket.write_all(response.as_bytes()).await.unwrap();
+      }
+      requests
+    });
+
+    let client = HttpClient::new(HttpConfig { cache: Some(HttpCacheConfig::default()), ..Default::default() });
+    let request = HttpRequest::new(&format!("http://{}/config.json", addr), Method::Get);
+    assert_eq!(client.fetch(&request).await.unwrap().body, b"hello");
+
+    let revalidated = client.fetch(&request).await.unwrap();
+    assert_eq!(revalidated.status, 200);
+    assert_eq!(revalidated.body, b"hello");
+
+    // fresh now - served without hitting the server
+    assert_eq!(client.fetch(&request).await.unwrap().body, b"hello");
+
+    let requests = server.await.unwrap();
+    assert!(requests[1].to_ascii_lowercase().contains("if-none-match: \"v1\""));
+    let stats = client.cache().unwrap().lock().unwrap().stats();
+    assert_eq!((stats.entries, stats.hits, stats.revalidations), (1, 1, 1));
+  }
+
+  #[tokio::test]
+  async fn test_client_cache_eviction_during_revalidation() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let client = HttpClient::new(HttpConfig { cache: Some(HttpCacheConfig::default()), ..Default::default() });
+    let cache = client.cache().unwrap().clone();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+      let mut requests = vec![];
+      for response in [
+        "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n",
+        "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v2\"\r\nContent-Length: 8\r\nConnection: close\r\n\r\nhello v2",
+      ] {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 1024];
+        let len = socket.read(&mut buf).await.unwrap();
+        requests.push(String::from_utf8_lossy(&buf[..len]).to_ascii_lowercase());
+        if response.contains("304") {
+          // e.g. evicted by a concurrent request
+          cache.lock().unwrap().clear();
+        }
+        socket.write_all(response.as_bytes()).await.unwrap();
+      }
+      requests
+    });
+
+    let request = HttpRequest::new(&format!("http://{}/config.json", addr), Method::Get);
+    assert_eq!(client.fetch(&request).await.unwrap().body, b"hello");
+    let response = client.fetch(&request).await.unwrap();
+    assert_eq!((response.status, response.body.as_slice(), response.attempts), (200, b"hello v2".as_slice(), 2));
+
+    let requests = server.await.unwrap();
+    assert!(requests[1].contains("if-none-match"));
+    assert!(!requests[2].contains("if-none-match"));
+  }
+
+  #[tokio::test]
+  async fn test_client_retry() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      for response in [
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+      ] {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 1024];
+        let _ = socket.read(&mut buf).await.unwrap();
+        socket.write_all(response.as_bytes()).await.unwrap();
+      }
+    });
+
+    let retry = RetryPolicy { max_attempts: 3, initial_backoff: 1, jitter: false, ..Default::default() };
+    let client = HttpClient::new(HttpConfig::default()).with_retry(retry);
+    let url = format!("http://{}/", addr);
+
+    let response = client.fetch(&HttpRequest::new(&url, Method::Get)).await.unwrap();
+    assert_eq!((response.status, response.attempts), (200, 2));
+    assert_eq!(response.body, b"ok");
+
+    // POST is not idempotent - the 503 is returned as is
+    let response = client.fetch(&HttpRequest::new(&url, Method::Post)).await.unwrap();
+    assert_eq!((response.status, response.attempts), (503, 1));
+
+    // guest policies cannot exceed the runtime policy
+    let greedy = RetryPolicy { max_attempts: 1_000, max_backoff: u64::MAX, ..Default::default() };
+    let policy = client.retry_policy(&HttpRequest::new(&url, Method::Get).with_retry(greedy));
+    assert_eq!((policy.max_attempts, policy.max_backoff), (3, 10_000));
+  }
+
+  #[tokio::test]
+  async fn test_retries_are_charged() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::quota::{Extension, Quota, QuotaConfig};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      while let Ok((mut socket, _)) = listener.accept().await {
+        let mut buf = [0u8; 1024];
+        let _ = socket.read(&mut buf).await.unwrap();
+        let response = "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        socket.write_all(response.as_bytes()).await.unwrap();
+      }
+    });
+
+    let quotas = Quotas::new(QuotaConfig { http: Quota { max_calls: Some(2), ..Default::default() }, ..Default::default() });
+    let retry = RetryPolicy { max_attempts: 5, initial_backoff: 1, jitter: false, ..Default::default() };
+    let client = HttpClient::new(HttpConfig::default()).with_retry(retry).with_quotas(quotas.clone());
+
+    // the call itself is admitted by the runtime, its retries by the client
+    let _permit = quotas.acquire(Extension::Http, 0, 0.0).unwrap();
+    let response = client.fetch(&HttpRequest::new(&format!("http://{}/", addr), Method::Get)).await.unwrap();
+    assert_eq!((response.status, response.attempts), (503, 2));
+    let usage = quotas.usage(Extension::Http);
+    assert_eq!((usage.calls, usage.rejected), (2, 1));
+  }
+
+  #[tokio::test]
+  async fn test_fetch_timeout() {
+    // accept connections but never respond
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      let mut sockets = vec![];
+      while let Ok((socket, _)) = listener.accept().await {
+        sockets.push(socket);
+      }
+    });
+
+    let request = HttpRequest::new(&format!("http://{}/", addr), Method::Get);
+    let config = HttpConfig { timeout: Some(50), ..Default::default() };
+    assert_eq!(request.fetch(&config).await.unwrap_err(), HttpError::Timeout(50));
+
+    let request = HttpRequest { timeout: Some(20), ..request };
+    assert_eq!(request.fetch(&config).await.unwrap_err(), HttpError::Timeout(20));
+  }
+}
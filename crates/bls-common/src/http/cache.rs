@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::http::{HttpHeaders, HttpRequest, HttpResponse, Method};
+
+/// Cache settings (`BlocklessConfig.http.cache`); the cache is disabled unless configured.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpCacheConfig {
+  /// upper bound of the cached response sizes (body and headers) in bytes
+  #[serde(default = "HttpCacheConfig::default_max_bytes")]
+  pub max_bytes: u64,
+}
+
+impl HttpCacheConfig {
+  fn default_max_bytes() -> u64 {
+    16 * 1024 * 1024
+  }
+}
+
+impl Default for HttpCacheConfig {
+  fn default() -> Self {
+    HttpCacheConfig { max_bytes: Self::default_max_bytes() }
+  }
+}
+
+/// Snapshot of the cache state, exposed through the `Blockless` js api.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpCacheStats {
+  pub entries: usize,
+  pub bytes: u64,
+  pub max_bytes: u64,
+  pub hits: u64,
+  pub misses: u64,
+  pub revalidations: u64,
+  pub urls: Vec<String>,
+}
+
+/// Result of looking up a request in the cache.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheLookup {
+  /// fresh response that can be returned without a request
+  Fresh(HttpResponse),
+  /// stale response; the request must be revalidated with these conditional headers
+  Stale(HttpHeaders),
+  Miss,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+  response: HttpResponse,
+  /// request header values named by the response `Vary` header
+  vary: Vec<(String, Option<String>)>,
+  /// time (in seconds) until which the entry is fresh
+  fresh_until: u64,
+  size: u64,
+  last_used: u64,
+}
+
+/// In-memory, size bounded (least recently used entries are evicted first) cache of `GET` responses.
+///
+/// Freshness follows `Cache-Control: max-age` (minus `Age`); `no-store` and `private` responses are never
+/// stored and `no-cache` responses are always revalidated. The cache is shared by all guests of the runtime,
+/// hence requests carrying `Authorization` bypass it. Stale entries carrying an `ETag` or `Last-Modified`
+/// validator are revalidated with `If-None-Match`/`If-Modified-Since`.
+#[derive(Debug, Clone, Default)]
+pub struct HttpCache {
+  max_bytes: u64,
+  bytes: u64,
+  entries: HashMap<String, CacheEntry>,
+  tick: u64,
+  hits: u64,
+  misses: u64,
+  revalidations: u64,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CacheControl {
+  max_age: Option<u64>,
+  no_cache: bool,
+  no_store: bool,
+  private: bool,
+}
+
+impl CacheControl {
+  fn parse(headers: &HttpHeaders) -> Self {
+    let mut cache_control = CacheControl::default();
+    for directive in headers.get_all("cache-control").flat_map(|value| value.split(',')) {
+      let (name, value) = match directive.split_once('=') {
+        Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+        None => (directive.trim(), None),
+      };
+      match name.to_ascii_lowercase().as_str() {
+        "max-age" => cache_control.max_age = value.and_then(|v| v.parse().ok()),
+        "no-cache" => cache_control.no_cache = true,
+        "no-store" => cache_control.no_store = true,
+        "private" => cache_control.private = true,
+        _ => {}
+      }
+    }
+    cache_control
+  }
+}
+
+impl HttpCache {
+  pub fn new(config: &HttpCacheConfig) -> Self {
+    HttpCache {
+      max_bytes: config.max_bytes,
+      ..Default::default()
+    }
+  }
+
+  /// Returns true if the request may be served from/stored in the cache.
+  /// Requests that carry credentials or their own validators, or opt out with `no-store`, bypass the cache.
+  pub fn is_cacheable(request: &HttpRequest) -> bool {
+    if request.method != Method::Get || request.stream {
+      return false;
+    }
+    match &request.headers {
+      Some(headers) => {
+        !headers.contains("authorization")
+          && !headers.contains("if-none-match")
+          && !headers.contains("if-modified-since")
+          && !headers.contains("range")
+          && !CacheControl::parse(headers).no_store
+      }
+      None => true,
+    }
+  }
+
+  fn key(request: &HttpRequest) -> String {
+    format!("{} {}", String::from_utf8_lossy(request.method.as_ref()), request.url)
+  }
+
+  fn vary_values(request: &HttpRequest, names: &[String]) -> Vec<(String, Option<String>)> {
+    names
+      .iter()
+      .map(|name| {
+        let value = request.headers.as_ref().and_then(|headers| headers.get(name)).map(str::to_string);
+        (name.to_ascii_lowercase(), value)
+      })
+      .collect()
+  }
+
+  pub fn lookup(&mut self, request: &HttpRequest, now: u64) -> CacheLookup {
+    self.tick += 1;
+    let key = Self::key(request);
+    let entry = match self.entries.get_mut(&key) {
+      Some(entry) => entry,
+      None => {
+        self.misses += 1;
+        return CacheLookup::Miss;
+      }
+    };
+    let vary_names: Vec<String> = entry.vary.iter().map(|(name, _)| name.clone()).collect();
+    if Self::vary_values(request, &vary_names) != entry.vary {
+      self.misses += 1;
+      return CacheLookup::Miss;
+    }
+    entry.last_used = self.tick;
+
+    let request_no_cache = request
+      .headers
+      .as_ref()
+      .map(|headers| CacheControl::parse(headers).no_cache)
+      .unwrap_or(false);
+    if now < entry.fresh_until && !request_no_cache {
+      self.hits += 1;
+      return CacheLookup::Fresh(entry.response.clone());
+    }
+
+    let mut conditional = HttpHeaders::new();
+    if let Some(etag) = entry.response.headers.get("etag") {
+      conditional.append("If-None-Match", etag);
+    }
+    if let Some(last_modified) = entry.response.headers.get("last-modified") {
+      conditional.append("If-Modified-Since", last_modified);
+    }
+    if conditional.is_empty() {
+      self.misses += 1;
+      return CacheLookup::Miss;
+    }
+    CacheLookup::Stale(conditional)
+  }
+
+  /// Stores a response; responses that are not cacheable are ignored.
+  pub fn store(&mut self, request: &HttpRequest, response: &HttpResponse, now: u64) {
+    let key = Self::key(request);
+    if response.status != 200 || response.body_handle.is_some() {
+      return;
+    }
+    let cache_control = CacheControl::parse(&response.headers);
+    let has_validator = response.headers.contains("etag") || response.headers.contains("last-modified");
+    if cache_control.no_store || cache_control.private || (cache_control.max_age.is_none() && !has_validator) {
+      return;
+    }
+    let vary_names: Vec<String> = response
+      .headers
+      .get_all("vary")
+      .flat_map(|value| value.split(','))
+      .map(|name| name.trim().to_string())
+      .filter(|name| !name.is_empty())
+      .collect();
+    if vary_names.iter().any(|name| name == "*") {
+      return;
+    }
+
+    let age = response.headers.get("age").and_then(|age| age.trim().parse::<u64>().ok()).unwrap_or(0);
+    let fresh_until = match cache_control.max_age {
+      Some(max_age) if !cache_control.no_cache => now + max_age.saturating_sub(age),
+      _ => now,
+    };
+    let size = response.body.len() as u64
+      + response.headers.iter().map(|(k, v)| (k.len() + v.len()) as u64).sum::<u64>();
+    if size > self.max_bytes {
+      self.remove(&key);
+      return;
+    }
+
+    self.remove(&key);
+    while self.bytes + size > self.max_bytes {
+      self.evict_lru();
+    }
+    self.tick += 1;
+    self.bytes += size;
+    self.entries.insert(key, CacheEntry {
+      response: response.clone(),
+      vary: Self::vary_values(request, &vary_names),
+      fresh_until,
+      size,
+      last_used: self.tick,
+    });
+  }
+
+  /// Handles a `304 Not Modified` revalidation response: refreshes the stored entry and returns it.
+  pub fn revalidate(&mut self, request: &HttpRequest, not_modified: &HttpResponse, now: u64) -> Option<HttpResponse> {
+    let mut response = self.entries.get(&Self::key(request))?.response.clone();
+    // the 304 response carries the updated caching headers
+    for (name, _) in not_modified.headers.iter() {
+      response.headers.remove(name);
+    }
+    for (name, value) in not_modified.headers.iter() {
+      response.headers.append(name, value);
+    }
+    self.revalidations += 1;
+    self.store(request, &response, now);
+    Some(response)
+  }
+
+  fn remove(&mut self, key: &str) {
+    if let Some(entry) = self.entries.remove(key) {
+      self.bytes -= entry.size;
+    }
+  }
+
+  fn evict_lru(&mut self) {
+    let lru = self
+      .entries
+      .iter()
+      .min_by_key(|(_, entry)| entry.last_used)
+      .map(|(key, _)| key.clone());
+    if let Some(key) = lru {
+      self.remove(&key);
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.clear();
+    self.bytes = 0;
+  }
+
+  pub fn stats(&self) -> HttpCacheStats {
+    let mut urls: Vec<String> = self.entries.keys().cloned().collect();
+    urls.sort();
+    HttpCacheStats {
+      entries: self.entries.len(),
+      bytes: self.bytes,
+      max_bytes: self.max_bytes,
+      hits: self.hits,
+      misses: self.misses,
+      revalidations: self.revalidations,
+      urls,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn response(headers: &[(&str, &str)], body: &str) -> HttpResponse {
+    HttpResponse {
+      status: 200,
+      headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+      body: body.as_bytes().to_vec(),
+      body_handle: None,
+      attempts: 1,
+    }
+  }
+
+  fn cache(max_bytes: u64) -> HttpCache {
+    HttpCache::new(&HttpCacheConfig { max_bytes })
+  }
+
+  #[test]
+  fn test_max_age_freshness() {
+    let mut cache = cache(1024);
+    let request = HttpRequest::new("https://example.com/config.json", Method::Get);
+    assert_eq!(cache.lookup(&request, 100), CacheLookup::Miss);
+
+    let resp = response(&[("Cache-Control", "public, max-age=60"), ("Age", "10")], "{}");
+    cache.store(&request, &resp, 100);
+    assert_eq!(cache.lookup(&request, 149), CacheLookup::Fresh(resp));
+    assert_eq!(cache.lookup(&request, 150), CacheLookup::Miss);
+
+    let stats = cache.stats();
+    assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 2));
+    assert_eq!(stats.urls, vec!["GET https://example.com/config.json"]);
+  }
+
+  #[test]
+  fn test_not_cacheable() {
+    let mut cache = cache(1024);
+    let request = HttpRequest::new("https://example.com/", Method::Get);
+    cache.store(&request, &response(&[("Cache-Control", "no-store, max-age=60")], "a"), 0);
+    cache.store(&request, &response(&[], "no freshness info"), 0);
+    cache.store(&request, &response(&[("Cache-Control", "max-age=60"), ("Vary", "*")], "a"), 0);
+    cache.store(&request, &response(&[("Cache-Control", "private, max-age=60")], "mine"), 0);
+    assert_eq!(cache.stats().entries, 0);
+
+    assert!(HttpCache::is_cacheable(&request));
+    assert!(!HttpCache::is_cacheable(&HttpRequest::new("https://example.com/", Method::Post)));
+    assert!(!HttpCache::is_cacheable(&request.clone().with_header("If-None-Match", "\"x\"")));
+    assert!(!HttpCache::is_cacheable(&request.clone().with_header("Cache-Control", "no-store")));
+    // another token must not be served the response for this one
+    assert!(!HttpCache::is_cacheable(&request.clone().with_header("Authorization", "Bearer alice")));
+  }
+
+  #[test]
+  fn test_revalidation() {
+    let mut cache = cache(1024);
+    let request = HttpRequest::new("https://example.com/data", Method::Get);
+    let resp = response(&[("Cache-Control", "no-cache"), ("ETag", "\"v1\""), ("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT")], "data");
+    cache.store(&request, &resp, 0);
+
+    let conditional = match cache.lookup(&request, 0) {
+      CacheLookup::Stale(conditional) => conditional,
+      lookup => panic!("unexpected lookup: {:?}", lookup),
+    };
+    assert_eq!(conditional.get("if-none-match"), Some("\"v1\""));
+    assert_eq!(conditional.get("if-modified-since"), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+
+    let not_modified = HttpResponse {
+      status: 304,
+      ..response(&[("Cache-Control", "max-age=30"), ("ETag", "\"v1\"")], "")
+    };
+    let revalidated = cache.revalidate(&request, &not_modified, 10).unwrap();
+    assert_eq!(revalidated.body, b"data");
+    assert_eq!(revalidated.headers.get("cache-control"), Some("max-age=30"));
+    assert_eq!(cache.lookup(&request, 20), CacheLookup::Fresh(revalidated));
+    assert_eq!(cache.stats().revalidations, 1);
+  }
+
+  #[test]
+  fn test_vary() {
+    let mut cache = cache(1024);
+    let json = HttpRequest::new("https://example.com/", Method::Get).with_header("Accept", "application/json");
+    let html = HttpRequest::new("https://example.com/", Method::Get).with_header("Accept", "text/html");
+    cache.store(&json, &response(&[("Cache-Control", "max-age=60"), ("Vary", "Accept")], "{}"), 0);
+    assert!(matches!(cache.lookup(&json, 1), CacheLookup::Fresh(_)));
+    assert_eq!(cache.lookup(&html, 1), CacheLookup::Miss);
+  }
+
+  #[test]
+  fn test_size_bound_evicts_least_recently_used() {
+    let mut cache = cache(80);
+    let fresh = [("Cache-Control", "max-age=60")];
+    let a = HttpRequest::new("https://example.com/a", Method::Get);
+    let b = HttpRequest::new("https://example.com/b", Method::Get);
+    let c = HttpRequest::new("https://example.com/c", Method::Get);
+    cache.store(&a, &response(&fresh, "aaaaaaaaaa"), 0);
+    cache.store(&b, &response(&fresh, "bbbbbbbbbb"), 0);
+    assert!(matches!(cache.lookup(&a, 1), CacheLookup::Fresh(_)));
+    cache.store(&c, &response(&fresh, "cccccccccc"), 0);
+
+    assert_eq!(cache.lookup(&b, 1), CacheLookup::Miss);
+    assert!(matches!(cache.lookup(&a, 1), CacheLookup::Fresh(_)));
+    assert!(matches!(cache.lookup(&c, 1), CacheLookup::Fresh(_)));
+    assert!(cache.stats().bytes <= 80);
+
+    cache.store(&a, &response(&fresh, &"x".repeat(100)), 0);
+    assert_eq!(cache.lookup(&a, 1), CacheLookup::Miss);
+
+    cache.clear();
+    assert_eq!(cache.stats().entries, 0);
+    assert_eq!(cache.stats().bytes, 0);
+  }
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde::{Deserialize, Serialize};
+use super::{HttpBodyStream, HttpConfig, HttpError, HttpHeaders, HttpRequest, HttpResponse, Method};
+
+/// Sends the requests of an `HttpClient`.
+pub trait HttpTransport: std::fmt::Debug + Send + Sync {
+  fn fetch<'a>(&'a self, request: &'a HttpRequest, config: &'a HttpConfig) -> LocalBoxFuture<'a, Result<HttpResponse, HttpError>>;
+
+  /// Defaults to buffering the whole response and streaming it from memory.
+  fn fetch_stream<'a>(
+    &'a self,
+    request: &'a HttpRequest,
+    config: &'a HttpConfig,
+  ) -> LocalBoxFuture<'a, Result<(HttpResponse, HttpBodyStream), HttpError>> {
+    async move {
+      let response = self.fetch(request, config).await?;
+      let body = HttpBodyStream::from_bytes(response.body.clone());
+      Ok((HttpResponse { body: vec![], ..response }, body))
+    }
+    .boxed_local()
+  }
+
+  /// Exchanges recorded so far; `None` unless the transport records.
+  fn recorded(&self) -> Option<Vec<HttpExchange>> {
+    None
+  }
+}
+
+/// Transport selection (`BlocklessConfig.http.transport`).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum HttpTransportConfig {
+  /// send requests over the network
+  #[default]
+  Live,
+  /// answer requests from a route table; unmatched requests fail
+  Mock { routes: Vec<MockRoute> },
+  /// send requests over the network and record the exchanges (see `HttpTransport::recorded`)
+  Record,
+  /// answer requests from previously recorded exchanges
+  #[serde(rename_all = "camelCase")]
+  Replay {
+    fixtures: Vec<HttpExchange>,
+    /// send requests without a recorded exchange over the network instead of failing them
+    #[serde(default)]
+    passthrough: bool,
+  },
+}
+
+impl HttpTransportConfig {
+  pub fn build(&self) -> Arc<dyn HttpTransport> {
+    match self {
+      HttpTransportConfig::Live => Arc::new(LiveTransport),
+      HttpTransportConfig::Mock { routes } => Arc::new(MockTransport::new(routes.clone())),
+      HttpTransportConfig::Record => Arc::new(RecordTransport::default()),
+      HttpTransportConfig::Replay { fixtures, passthrough } => Arc::new(ReplayTransport::new(fixtures.clone(), *passthrough)),
+    }
+  }
+}
+
+/// A recorded request and the response it received.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HttpExchange {
+  pub method: Method,
+  pub url: String,
+  pub response: HttpResponse,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LiveTransport;
+
+impl HttpTransport for LiveTransport {
+  fn fetch<'a>(&'a self, request: &'a HttpRequest, config: &'a HttpConfig) -> LocalBoxFuture<'a, Result<HttpResponse, HttpError>> {
+    request.fetch(config).boxed_local()
+  }
+
+  fn fetch_stream<'a>(
+    &'a self,
+    request: &'a HttpRequest,
+    config: &'a HttpConfig,
+  ) -> LocalBoxFuture<'a, Result<(HttpResponse, HttpBodyStream), HttpError>> {
+    request.fetch_stream(config).boxed_local()
+  }
+}
+
+/// Canned response of a `MockTransport`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MockRoute {
+  /// method name (e.g. `GET`); matches any method when not set
+  pub method: Option<String>,
+  /// exact url, or url prefix when ending with `*`
+  pub url: String,
+  #[serde(default = "default_status")]
+  pub status: u16,
+  #[serde(default)]
+  pub headers: HttpHeaders,
+  #[serde(default)]
+  pub body: String,
+}
+
+fn default_status() -> u16 {
+  200
+}
+
+impl MockRoute {
+  pub fn new(method: Option<&str>, url: &str, status: u16, body: &str) -> Self {
+    MockRoute { method: method.map(Into::into), url: url.into(), status, headers: HttpHeaders::new(), body: body.into() }
+  }
+
+  fn matches(&self, request: &HttpRequest) -> bool {
+    let url_matches = match self.url.strip_suffix('*') {
+      Some(prefix) => request.url.starts_with(prefix),
+      None => request.url == self.url,
+    };
+    let method_matches = match &self.method {
+      Some(method) => method.parse::<Method>().is_ok_and(|method| method == request.method),
+      None => true,
+    };
+    url_matches && method_matches
+  }
+}
+
+/// Answers requests with the first matching route.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+  routes: Vec<MockRoute>,
+}
+
+impl MockTransport {
+  pub fn new(routes: Vec<MockRoute>) -> Self {
+    MockTransport { routes }
+  }
+}
+
+impl HttpTransport for MockTransport {
+  fn fetch<'a>(&'a self, request: &'a HttpRequest, _config: &'a HttpConfig) -> LocalBoxFuture<'a, Result<HttpResponse, HttpError>> {
+    let response = match self.routes.iter().find(|route| route.matches(request)) {
+      Some(route) => Ok(HttpResponse {
+        status: route.status,
+        headers: route.headers.clone(),
+        body: route.body.as_bytes().to_vec(),
+        body_handle: None,
+        attempts: 1,
+      }),
+      None => Err(no_exchange(request, "mock route")),
+    };
+    futures::future::ready(response).boxed_local()
+  }
+}
+
+/// Sends requests over the network and keeps every exchange.
+#[derive(Debug, Clone, Default)]
+pub struct RecordTransport {
+  exchanges: Arc<Mutex<Vec<HttpExchange>>>,
+}
+
+impl HttpTransport for RecordTransport {
+  fn fetch<'a>(&'a self, request: &'a HttpRequest, config: &'a HttpConfig) -> LocalBoxFuture<'a, Result<HttpResponse, HttpError>> {
+    async move {
+      let response = request.fetch(config).await?;
+      self.exchanges.lock().unwrap().push(HttpExchange {
+        method: request.method.clone(),
+        url: request.url.clone(),
+        response: response.clone(),
+      });
+      Ok(response)
+    }
+    .boxed_local()
+  }
+
+  fn recorded(&self) -> Option<Vec<HttpExchange>> {
+    Some(self.exchanges.lock().unwrap().clone())
+  }
+}
+
+/// Answers requests from recorded exchanges; repeated requests replay the exchanges in recorded order
+/// and keep answering with the last one.
+#[derive(Debug, Default)]
+pub struct ReplayTransport {
+  exchanges: Mutex<HashMap<String, Vec<HttpResponse>>>,
+  passthrough: bool,
+}
+
+impl ReplayTransport {
+  pub fn new(fixtures: Vec<HttpExchange>, passthrough: bool) -> Self {
+    let mut exchanges: HashMap<String, Vec<HttpResponse>> = HashMap::new();
+    for exchange in fixtures {
+      exchanges
+        .entry(exchange_key(&exchange.method, &exchange.url))
+        .or_default()
+        .push(exchange.response);
+    }
+    ReplayTransport { exchanges: Mutex::new(exchanges), passthrough }
+  }
+
+  fn next_response(&self, request: &HttpRequest) -> Option<HttpResponse> {
+    let mut exchanges = self.exchanges.lock().unwrap();
+    let responses = exchanges.get_mut(&exchange_key(&request.method, &request.url))?;
+    match responses.len() {
+      0 => None,
+      1 => responses.first().cloned(),
+      _ => Some(responses.remove(0)),
+    }
+  }
+}
+
+impl HttpTransport for ReplayTransport {
+  fn fetch<'a>(&'a self, request: &'a HttpRequest, config: &'a HttpConfig) -> LocalBoxFuture<'a, Result<HttpResponse, HttpError>> {
+    async move {
+      match self.next_response(request) {
+        Some(response) => Ok(HttpResponse { attempts: 1, ..response }),
+        None if self.passthrough => request.fetch(config).await,
+        None => Err(no_exchange(request, "recorded exchange")),
+      }
+    }
+    .boxed_local()
+  }
+}
+
+fn exchange_key(method: &Method, url: &str) -> String {
+  format!("{} {}", String::from_utf8_lossy(method.as_ref()).to_ascii_uppercase(), url)
+}
+
+fn no_exchange(request: &HttpRequest, kind: &str) -> HttpError {
+  HttpError::Request(format!("no {} for {}", kind, exchange_key(&request.method, &request.url)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_mock_transport() {
+    let config: HttpTransportConfig = serde_json::from_str(r#"{
+      "mode": "mock",
+      "routes": [
+        {"method": "GET", "url": "https://api.example.com/todos/1", "body": "{\"id\":1}"},
+        {"url": "https://api.example.com/*", "status": 404}
+      ]
+    }"#).unwrap();
+    let transport = config.build();
+    let config = HttpConfig::default();
+
+    let request = HttpRequest::new("https://api.example.com/todos/1", Method::Get);
+    let response = transport.fetch(&request, &config).await.unwrap();
+    assert_eq!((response.status, response.body), (200, b"{\"id\":1}".to_vec()));
+
+    let request = HttpRequest::new("https://api.example.com/todos/1", Method::Delete);
+    assert_eq!(transport.fetch(&request, &config).await.unwrap().status, 404);
+
+    let request = HttpRequest::new("https://example.com/", Method::Get);
+    assert_eq!(
+      transport.fetch(&request, &config).await.unwrap_err(),
+      HttpError::Request("no mock route for GET https://example.com/".into())
+    );
+
+    let request = HttpRequest::new("https://api.example.com/todos/1", Method::Get);
+    let (head, mut body) = transport.fetch_stream(&request, &config).await.unwrap();
+    assert!(head.body.is_empty());
+    assert_eq!(body.read(4).await.unwrap(), b"{\"id");
+    assert_eq!(body.read(100).await.unwrap(), b"\":1}");
+    assert!(body.read(100).await.unwrap().is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_replay_transport() {
+    let response = |body: &str| HttpResponse {
+      status: 200,
+      headers: HttpHeaders::new(),
+      body: body.as_bytes().to_vec(),
+      body_handle: None,
+      attempts: 3,
+    };
+    let fixtures = vec![
+      HttpExchange { method: Method::Get, url: "https://example.com/counter".into(), response: response("1") },
+      HttpExchange { method: Method::Get, url: "https://example.com/counter".into(), response: response("2") },
+    ];
+    // fixtures survive a round trip through a fixture file
+    let fixtures = serde_json::from_str(&serde_json::to_string(&fixtures).unwrap()).unwrap();
+    let transport = ReplayTransport::new(fixtures, false);
+    let config = HttpConfig::default();
+
+    let request = HttpRequest::new("https://example.com/counter", Method::Get);
+    assert_eq!(transport.fetch(&request, &config).await.unwrap().body, b"1");
+    assert_eq!(transport.fetch(&request, &config).await.unwrap().body, b"2");
+    let replayed = transport.fetch(&request, &config).await.unwrap();
+    assert_eq!((replayed.body, replayed.attempts), (b"2".to_vec(), 1));
+
+    let request = HttpRequest::new("https://example.com/counter", Method::Post);
+    assert!(transport.fetch(&request, &config).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn test_record_transport() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      let (mut socket, _) = listener.accept().await.unwrap();
+      let mut buf = [0u8; 1024];
+      let _ = socket.read(&mut buf).await.unwrap();
+      socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello").await.unwrap();
+    });
+
+    let transport = HttpTransportConfig::Record.build();
+    let request = HttpRequest::new(&format!("http://{}/greeting", addr), Method::Get);
+    transport.fetch(&request, &HttpConfig::default()).await.unwrap();
+
+    let recorded = transport.recorded().unwrap();
+    assert_eq!(recorded.len(), 1);
+    assert_eq!(recorded[0].url, request.url);
+    assert_eq!(recorded[0].response.body, b"hello");
+
+    // the recording replays offline
+    let replay = ReplayTransport::new(recorded, false);
+    assert_eq!(replay.fetch(&request, &HttpConfig::default()).await.unwrap().body, b"hello");
+  }
+}
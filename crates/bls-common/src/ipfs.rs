@@ -3,72 +3,300 @@ use crate::{impl_display, impl_query_string_conversions};
 use std::str::FromStr;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "use-wasm-bindgen")]
+mod gateway;
+mod kubo;
+#[cfg(feature = "use-wasm-bindgen")]
+mod pinning;
+
+/// Runtime-wide IPFS settings (`BlocklessConfig.ipfs`).
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IPFSConfig {
+  /// base url of the node's RPC API, without `/api/v0` (default: `http://127.0.0.1:5001`)
+  pub api_url: Option<String>,
+  /// credentials sent with every RPC request, e.g. to a node behind an authenticating proxy;
+  /// never sent to the gateway
+  pub auth: Option<IPFSAuth>,
+  /// request timeout in milliseconds; covers sending the request and reading the response
+  pub timeout: Option<u64>,
+  /// serves `cat` and `get` from this trustless gateway (verifying every block) instead of the RPC API;
+  /// all other commands (except remote pins) are rejected
+  pub gateway_url: Option<String>,
+  /// credentials sent with every gateway request
+  pub gateway_auth: Option<IPFSAuth>,
+  /// serves the `pin/remote/*` commands
+  pub pinning_service: Option<PinningServiceConfig>,
+}
+
+/// An IPFS Pinning Services API endpoint; see https://ipfs.github.io/pinning-services-api-spec/
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PinningServiceConfig {
+  /// base url of the API, without `/pins`
+  pub endpoint: String,
+  /// sent as bearer token
+  pub access_token: String,
+}
+
+impl std::fmt::Debug for PinningServiceConfig {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("PinningServiceConfig").field("endpoint", &self.endpoint).finish_non_exhaustive()
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum IPFSAuth {
+  Bearer { token: String },
+  Basic { username: String, password: Option<String> },
+}
+
+impl IPFSAuth {
+  /// Value of the `Authorization` header.
+  pub fn header_value(&self) -> String {
+    use base64::Engine;
+    match self {
+      IPFSAuth::Bearer { token } => format!("Bearer {}", token),
+      IPFSAuth::Basic { username, password } => {
+        let credentials = format!("{}:{}", username, password.as_deref().unwrap_or_default());
+        format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials))
+      }
+    }
+  }
+}
+
+impl std::fmt::Debug for IPFSAuth {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      IPFSAuth::Bearer { .. } => f.debug_struct("Bearer").finish_non_exhaustive(),
+      IPFSAuth::Basic { username, .. } => f.debug_struct("Basic").field("username", username).finish_non_exhaustive(),
+    }
+  }
+}
+
 /// declare IPFS client behind feature flag - since reqwest is not supported in wasm32-unknown-unknown targets
 #[cfg(feature = "use-wasm-bindgen")]
 pub mod client {
+  use std::sync::Arc;
+  use std::sync::atomic::{AtomicU32, Ordering};
+  use crate::quota::{Extension, Quotas};
+  use crate::retry::RetryPolicy;
+  use crate::time::with_timeout;
+  use super::{IPFSAuth, IPFSConfig};
+
+  const DEFAULT_API_URL: &str = "http://127.0.0.1:5001";
+
+  /// Read-only RPC commands; only these are retried unless the retry policy says otherwise.
+  const IDEMPOTENT_COMMANDS: &[&str] = &[
+    "version", "files/ls", "files/read", "files/stat", "cat", "get", "ls", "pin/ls", "pin/remote/ls",
+  ];
+
   #[derive(Debug, Clone)]
   pub struct IPFSClient {
     client: reqwest::Client,
     url: reqwest::Url,
+    gateway_url: Option<reqwest::Url>,
+    gateway_auth: Option<IPFSAuth>,
+    pinning_service: Option<(reqwest::Url, IPFSAuth)>,
+    auth: Option<IPFSAuth>,
+    timeout: Option<u64>,
+    retry: RetryPolicy,
+    quotas: Option<Quotas>,
+    /// network attempts made by this client; see `counting`
+    attempts: Arc<AtomicU32>,
   }
 
   impl Default for IPFSClient {
     fn default() -> Self {
-      IPFSClient {
-        client: reqwest::Client::new(),
-        url: reqwest::Url::parse("http://127.0.0.1:5001").unwrap(),
-      }
+      IPFSClient::new(IPFSConfig::default()).unwrap()
     }
   }
 
   impl IPFSClient {
-    pub fn new(url: reqwest::Url) -> Self {
-      IPFSClient {
+    pub fn new(config: IPFSConfig) -> Result<Self, String> {
+      let url = parse_url("api", config.api_url.as_deref().unwrap_or(DEFAULT_API_URL))?;
+      let gateway_url = config.gateway_url.as_deref().map(|url| parse_url("gateway", url)).transpose()?;
+      let pinning_service = match config.pinning_service {
+        Some(service) => Some((
+          parse_url("pinning service", &service.endpoint)?,
+          IPFSAuth::Bearer { token: service.access_token },
+        )),
+        None => None,
+      };
+      Ok(IPFSClient {
         client: reqwest::Client::new(),
         url,
-      }
+        gateway_url,
+        gateway_auth: config.gateway_auth,
+        pinning_service,
+        auth: config.auth,
+        timeout: config.timeout,
+        retry: RetryPolicy::default(),
+        quotas: None,
+        attempts: Default::default(),
+      })
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+      self.retry = retry;
+      self
+    }
+
+    /// Charges every retry against the `ipfs` quota, like a call of its own.
+    pub fn with_quotas(mut self, quotas: Quotas) -> Self {
+      self.quotas = Some(quotas);
+      self
+    }
+
+    /// A clone that counts its network attempts separately, e.g. for the requests of a single command.
+    pub fn counting(&self) -> Self {
+      IPFSClient { attempts: Default::default(), ..self.clone() }
+    }
+
+    /// Network attempts made since the client was created (or `counting` was called).
+    pub fn attempts(&self) -> u32 {
+      self.attempts.load(Ordering::Relaxed)
     }
 
     pub fn api_url(&self) -> String {
-      format!("{}api/v0", self.url)
+      format!("{}/api/v0", self.url.as_str().trim_end_matches('/'))
+    }
+
+    pub fn gateway_url(&self) -> Option<&reqwest::Url> {
+      self.gateway_url.as_ref()
+    }
+
+    /// Fetches a gateway url; `command` selects the retry behaviour like for RPC calls.
+    pub async fn get(&self, command: &str, url: &str, accept: &str) -> Result<Vec<u8>, String> {
+      let (status, bytes) = self
+        .send(command, || {
+          let request = self.client.get(url).header(reqwest::header::ACCEPT, accept);
+          match &self.gateway_auth {
+            Some(auth) => request.header(reqwest::header::AUTHORIZATION, auth.header_value()),
+            None => request,
+          }
+        })
+        .await?;
+      if status != 200 {
+        return Err(format!("Error get response: {}", status));
+      }
+      Ok(bytes)
     }
 
     pub async fn post(&self, command: &impl ToString) -> Result<Vec<u8>, String> {
-      let url = format!("{}/{}", &self.api_url(), command.to_string());
-      let response = self.client
-          .post(url)
-          .send()
-          .await
-          .map_err(|e| format!("Error sending request: {:?}", e))?;
-      if response.status() != 200 {
-        return Err(format!("Error post response: {:?}", response.status()));
+      let command = command.to_string();
+      let url = format!("{}/{}", &self.api_url(), command);
+      let (status, bytes) = self.send(&command, || self.authorized(self.client.post(&url))).await?;
+      if status != 200 {
+        return Err(format!("Error post response: {}", status));
       }
-      Ok(response.bytes().await.map_err(|_| "response body error")?.to_vec())
+      Ok(bytes)
     }
 
-    pub async fn post_form(&self, command: &impl ToString, file_field_name: &str, file_data: Vec<u8>) -> Result<Vec<u8>, String> {
-      let url = format!("{}/{}", &self.api_url(), command.to_string());
+    /// Posts the files (name, content) as multipart form, one part per file.
+    pub async fn post_form(&self, command: &impl ToString, file_field_name: &str, files: &[(String, Vec<u8>)]) -> Result<Vec<u8>, String> {
+      let command = command.to_string();
+      let url = format!("{}/{}", &self.api_url(), command);
+
+      // perform the request; the form is rebuilt for every attempt
+      let (status, bytes) = self
+        .send(&command, || {
+          // add file data
+          let form = files.iter().fold(reqwest::multipart::Form::new(), |form, (name, data)| {
+            let part = reqwest::multipart::Part::bytes(data.clone()).file_name(name.clone());
+            form.part(file_field_name.to_owned(), part)
+          });
+          self.authorized(self.client.post(&url)).multipart(form)
+        })
+        .await?;
 
-      let mut form = reqwest::multipart::Form::new();
+      if status != 200 {
+        return Err(format!("Error post_form response: {}", status));
+      }
 
-      // add file data
-      let part = reqwest::multipart::Part::bytes(file_data).file_name("file");
-      form = form.part(file_field_name.to_owned(), part);
+      Ok(bytes)
+    }
 
-      // perform the request
-      let response = self.client
-        .post(&url)
-        .multipart(form)
-        .send()
+    /// Calls the remote pinning service; `path` is relative to its endpoint (e.g. `pins/<requestid>`).
+    /// Responds with the status and body, whatever the status.
+    pub async fn pinning_service_call(
+      &self,
+      command: &str,
+      method: reqwest::Method,
+      path: &str,
+      query: &[(&str, String)],
+      body: Option<Vec<u8>>,
+    ) -> Result<(u16, Vec<u8>), String> {
+      let (endpoint, auth) = self.pinning_service.as_ref().ok_or("no remote pinning service configured")?;
+      let url = format!("{}/{}", endpoint.as_str().trim_end_matches('/'), path);
+      self
+        .send(command, || {
+          let request = self.client
+            .request(method.clone(), &url)
+            .query(query)
+            .header(reqwest::header::AUTHORIZATION, auth.header_value());
+          match &body {
+            Some(body) => request.header(reqwest::header::CONTENT_TYPE, "application/json").body(body.clone()),
+            None => request,
+          }
+        })
         .await
-        .map_err(|e| format!("Error sending request: {:?}", e))?;
+    }
 
-      if response.status().as_u16() != 200 {
-        return Err(format!("Error post_form response: {:?}", response.status()));
+    /// Adds the credentials of the node; only for RPC requests.
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+      match &self.auth {
+        Some(auth) => request.header(reqwest::header::AUTHORIZATION, auth.header_value()),
+        None => request,
       }
+    }
 
-      Ok(response.bytes().await.map_err(|_| "response body error")?.to_vec())
+    /// Performs the request (with retries); responds with the status and body of the last attempt.
+    async fn send(
+      &self,
+      command: &str,
+      request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<(u16, Vec<u8>), String> {
+      let path = command.split('?').next().unwrap_or_default();
+      let (response, attempts) = self.retry
+        .run_charged(
+          IDEMPOTENT_COMMANDS.contains(&path),
+          || self.attempt(request()),
+          |result| match result {
+            Ok((status, _)) => self.retry.is_retryable_status(*status),
+            Err(_) => true,
+          },
+          || match &self.quotas {
+            Some(quotas) => quotas.charge_retry(Extension::Ipfs, crate::time::now_secs()).is_ok(),
+            None => true,
+          },
+        )
+        .await;
+      self.attempts.fetch_add(attempts, Ordering::Relaxed);
+      response
     }
+
+    async fn attempt(&self, request: reqwest::RequestBuilder) -> Result<(u16, Vec<u8>), String> {
+      let response = async {
+        let response = request.send().await.map_err(|e| format!("Error sending request: {:?}", e))?;
+        let status = response.status().as_u16();
+        let bytes = response.bytes().await.map_err(|_| "response body error".to_string())?;
+        Ok((status, bytes.to_vec()))
+      };
+      match self.timeout {
+        Some(timeout) => with_timeout(response, timeout, |timeout| format!("request timed out after {}ms", timeout)).await,
+        None => response.await,
+      }
+    }
+  }
+
+  fn parse_url(kind: &str, url: &str) -> Result<reqwest::Url, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid ipfs {} url \"{}\": {}", kind, url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+      return Err(format!("invalid ipfs {} url \"{}\": only http and https are supported", kind, url));
+    }
+    Ok(parsed)
   }
 }
 
@@ -76,34 +304,132 @@ pub mod client {
 pub enum IPFSCommand {
   FilesChCid(FilesChCidOpts),
   FilesCp(FilesCpOpts),
+  /// responds with a json `FilesLsResponse`
   FilesLs(FilesLsOpts),
   FilesMkdir(FilesMkdirOpts),
   FilesMv(FilesMvOpts),
+  /// responds with the file content
   FilesRead(FilesReadOpts),
   FilesRm(FilesRmOpts),
+  /// responds with a json `FilesStatResponse`
   FilesStat(FilesStatOpts),
   FilesWrite(FilesWriteOpts),
+  /// responds with a json `VersionResponse`
   Version(VersionOpts),
+  /// adds the files and responds with a json list of `AddResponse` (the wrapping directory last)
+  Add(AddOpts, Vec<AddFile>),
+  /// responds with the file content
+  Cat(CatOpts),
+  /// responds with a tar archive of the file or directory
+  Get(GetOpts),
+  /// responds with a json `LsResponse`
+  Ls(LsOpts),
+  /// responds with a json `PinResponse`
+  PinAdd(PinAddOpts),
+  /// responds with a json `PinLsResponse`
+  PinLs(PinLsOpts),
+  /// responds with a json `PinResponse`
+  PinRm(PinRmOpts),
+  /// responds with a json `PinResponse` (the old and the new CID)
+  PinUpdate(PinUpdateOpts),
+  /// requests a pin at the remote pinning service; responds with a json `RemotePin`
+  PinRemoteAdd(PinRemoteAddOpts),
+  /// responds with a json `PinRemoteLsResponse`
+  PinRemoteLs(PinRemoteLsOpts),
+  /// removes a pin request from the remote pinning service
+  PinRemoteRm(PinRemoteRmOpts),
+}
+
+impl IPFSCommand {
+  /// RPC path of the command (e.g. `files/read`), as matched by `ipfs://` permissions.
+  pub fn name(&self) -> &'static str {
+    match self {
+      IPFSCommand::FilesChCid(_) => "files/chcid",
+      IPFSCommand::FilesCp(_) => "files/cp",
+      IPFSCommand::FilesLs(_) => "files/ls",
+      IPFSCommand::FilesMkdir(_) => "files/mkdir",
+      IPFSCommand::FilesMv(_) => "files/mv",
+      IPFSCommand::FilesRead(_) => "files/read",
+      IPFSCommand::FilesRm(_) => "files/rm",
+      IPFSCommand::FilesStat(_) => "files/stat",
+      IPFSCommand::FilesWrite(_) => "files/write",
+      IPFSCommand::Version(_) => "version",
+      IPFSCommand::Add(..) => "add",
+      IPFSCommand::Cat(_) => "cat",
+      IPFSCommand::Get(_) => "get",
+      IPFSCommand::Ls(_) => "ls",
+      IPFSCommand::PinAdd(_) => "pin/add",
+      IPFSCommand::PinLs(_) => "pin/ls",
+      IPFSCommand::PinRm(_) => "pin/rm",
+      IPFSCommand::PinUpdate(_) => "pin/update",
+      IPFSCommand::PinRemoteAdd(_) => "pin/remote/add",
+      IPFSCommand::PinRemoteLs(_) => "pin/remote/ls",
+      IPFSCommand::PinRemoteRm(_) => "pin/remote/rm",
+    }
+  }
+
+  /// Returns true if the permissions allow the command.
+  pub fn valid_permissions(&self, permissions: &crate::permissions::Permissions) -> bool {
+    permissions.allows_ipfs(self.name())
+  }
 }
 
 #[cfg(feature = "use-wasm-bindgen")]
 impl IPFSCommand {
+  /// Like `exec`, also reporting the number of network attempts made for the command.
+  pub async fn exec_counted(&self, client: &crate::ipfs::client::IPFSClient) -> crate::retry::CallResult<String> {
+    let client = client.counting();
+    let result = self.exec(&client).await;
+    crate::retry::CallResult { result, attempts: client.attempts() }
+  }
+
   pub async fn exec(&self, client: &crate::ipfs::client::IPFSClient) -> Result<Vec<u8>, String> {
+    // remote pins are served by the pinning service, also in gateway mode
+    if client.gateway_url().is_some() && !self.name().starts_with("pin/remote/") {
+      return gateway::exec(self, client).await;
+    }
     match self {
       IPFSCommand::FilesChCid(opts) => client.post(opts).await,
       IPFSCommand::FilesCp(opts) => client.post(opts).await,
-      IPFSCommand::FilesLs(opts) => client.post(opts).await,
+      IPFSCommand::FilesLs(opts) => to_json(&FilesLsResponse::from(kubo::parse::<kubo::FilesLs>(&client.post(opts).await?)?)),
       IPFSCommand::FilesMkdir(opts) => client.post(opts).await,
       IPFSCommand::FilesMv(opts) => client.post(opts).await,
       IPFSCommand::FilesRead(opts) => client.post(opts).await,
       IPFSCommand::FilesRm(opts) => client.post(opts).await,
-      IPFSCommand::FilesStat(opts) => client.post(opts).await,
-      IPFSCommand::FilesWrite(opts) => client.post_form(opts, "file", opts.data.clone()).await,
-      IPFSCommand::Version(opts) => client.post(opts).await,
+      IPFSCommand::FilesStat(opts) => {
+        to_json(&FilesStatResponse::from(kubo::parse::<kubo::FilesStat>(&client.post(opts).await?)?))
+      }
+      IPFSCommand::FilesWrite(opts) => client.post_form(opts, "file", &[("file".into(), opts.data.clone())]).await,
+      IPFSCommand::Version(opts) => to_json(&VersionResponse::from(kubo::parse::<kubo::Version>(&client.post(opts).await?)?)),
+      IPFSCommand::Add(opts, files) => {
+        if files.is_empty() {
+          return Err("no files to add".into());
+        }
+        if let Some(file) = files.iter().find(|file| file.name.is_empty() || file.name.contains('/')) {
+          return Err(format!("invalid file name \"{}\": directories are not supported", file.name));
+        }
+        let files: Vec<_> = files.iter().map(|file| (file.name.clone(), file.data.clone())).collect();
+        to_json(&kubo::parse_add(&client.post_form(opts, "file", &files).await?)?)
+      }
+      IPFSCommand::Cat(opts) => client.post(opts).await,
+      IPFSCommand::Get(opts) => client.post(opts).await,
+      IPFSCommand::Ls(opts) => to_json(&LsResponse::from(kubo::parse::<kubo::Ls>(&client.post(opts).await?)?)),
+      IPFSCommand::PinAdd(opts) => to_json(&PinResponse::from(kubo::parse::<kubo::Pins>(&client.post(opts).await?)?)),
+      IPFSCommand::PinLs(opts) => to_json(&PinLsResponse::from(kubo::parse::<kubo::PinLs>(&client.post(opts).await?)?)),
+      IPFSCommand::PinRm(opts) => to_json(&PinResponse::from(kubo::parse::<kubo::Pins>(&client.post(opts).await?)?)),
+      IPFSCommand::PinUpdate(opts) => to_json(&PinResponse::from(kubo::parse::<kubo::Pins>(&client.post(opts).await?)?)),
+      IPFSCommand::PinRemoteAdd(opts) => to_json(&pinning::add(client, opts).await?),
+      IPFSCommand::PinRemoteLs(opts) => to_json(&pinning::ls(client, opts).await?),
+      IPFSCommand::PinRemoteRm(opts) => pinning::rm(client, opts).await.map(|_| vec![]),
     }
   }
 }
 
+#[cfg(feature = "use-wasm-bindgen")]
+fn to_json(value: &impl Serialize) -> Result<Vec<u8>, String> {
+  serde_json::to_vec(value).map_err(|e| e.to_string())
+}
+
 impl_display!(IPFSCommand);
 
 // https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-files-chcid
@@ -145,6 +471,30 @@ impl Default for FilesLsOpts {
 }
 impl_query_string_conversions!("files/ls?", FilesLsOpts);
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FilesLsResponse {
+  pub entries: Vec<FilesEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FilesEntry {
+  pub name: String,
+  pub entry_type: IPFSEntryType,
+  /// only set with `long`
+  pub size: u64,
+  /// only set with `long`
+  pub hash: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IPFSEntryType {
+  File,
+  Directory,
+  Symlink,
+  Other,
+}
+
 // https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-files-mkdir
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FilesMkdirOpts {
@@ -218,6 +568,19 @@ pub struct FilesStatOpts {
 }
 impl_query_string_conversions!("files/stat?", FilesStatOpts);
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FilesStatResponse {
+  pub hash: String,
+  pub size: u64,
+  /// size including all blocks of the DAG
+  pub cumulative_size: u64,
+  pub blocks: u64,
+  pub entry_type: IPFSEntryType,
+  /// only set with `with_local`
+  pub local: Option<bool>,
+  pub size_local: Option<u64>,
+}
+
 // https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-files-write
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FilesWriteOpts {
@@ -245,6 +608,279 @@ pub struct VersionOpts {
 }
 impl_query_string_conversions!("version?", VersionOpts);
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct VersionResponse {
+  pub version: String,
+  pub commit: Option<String>,
+  pub repo: Option<String>,
+  pub system: Option<String>,
+  pub golang: Option<String>,
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-add
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct AddOpts {
+  #[serde(rename = "cid-version")]
+  pub cid_version: Option<u64>,
+  #[serde(rename = "raw-leaves")]
+  pub raw_leaves: Option<bool>,
+  /// pins the added content (the node defaults to true)
+  pub pin: Option<bool>,
+  /// wraps the files into a directory; its CID is the last line of the response
+  #[serde(rename = "wrap-with-directory")]
+  pub wrap_with_directory: Option<bool>,
+  pub hash: Option<String>,
+  /// only computes the CIDs without storing the content
+  #[serde(rename = "only-hash")]
+  pub only_hash: Option<bool>,
+}
+impl_query_string_conversions!("add?", AddOpts);
+
+/// A file of an `IPFSCommand::Add` call.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AddFile {
+  /// file name (without directories)
+  pub name: String,
+  pub data: Vec<u8>,
+}
+
+/// An added file (or wrapping directory).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AddResponse {
+  pub name: String,
+  /// CID of the content
+  pub hash: String,
+  pub size: u64,
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-cat
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CatOpts {
+  /// CID or IPFS path (e.g. `/ipfs/<cid>/file.txt`)
+  pub arg: String,
+  pub offset: Option<u64>,
+  pub length: Option<u64>,
+}
+impl_query_string_conversions!("cat?", CatOpts);
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-get
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GetOpts {
+  /// CID or IPFS path (e.g. `/ipfs/<cid>/dir`)
+  pub arg: String,
+  /// always output a tar archive, also for a single file
+  pub archive: Option<bool>,
+  /// gzip the output
+  pub compress: Option<bool>,
+  #[serde(rename = "compression-level")]
+  pub compression_level: Option<u8>,
+}
+impl_query_string_conversions!("get?", GetOpts);
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-ls
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LsOpts {
+  /// CID or IPFS path of a directory
+  pub arg: String,
+  #[serde(rename = "resolve-type")]
+  pub resolve_type: Option<bool>,
+  pub size: Option<bool>,
+}
+impl_query_string_conversions!("ls?", LsOpts);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LsResponse {
+  pub objects: Vec<LsObject>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LsObject {
+  pub hash: String,
+  pub links: Vec<LsLink>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LsLink {
+  pub name: String,
+  pub hash: String,
+  pub size: u64,
+  /// `other` when not resolved (`resolve_type: false`)
+  pub entry_type: IPFSEntryType,
+  /// target of a symlink
+  pub target: Option<String>,
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pin-add
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PinAddOpts {
+  /// CID or IPFS path
+  pub arg: String,
+  /// also pins the linked blocks (the node defaults to true)
+  pub recursive: Option<bool>,
+  pub name: Option<String>,
+}
+impl_query_string_conversions!("pin/add?", PinAddOpts);
+
+/// CIDs of the pinned (or unpinned) content.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PinResponse {
+  pub pins: Vec<String>,
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pin-ls
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct PinLsOpts {
+  /// lists only this CID or IPFS path (the node fails if it is not pinned)
+  pub arg: Option<String>,
+  /// `all` (default), `direct`, `indirect` or `recursive`
+  #[serde(rename = "type")]
+  pub pin_type: Option<String>,
+  /// also lists the pin names
+  pub names: Option<bool>,
+}
+impl_query_string_conversions!("pin/ls?", PinLsOpts);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PinLsResponse {
+  /// sorted by CID
+  pub pins: Vec<Pin>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Pin {
+  pub cid: String,
+  pub pin_type: PinType,
+  /// only set with `names`
+  pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PinType {
+  Direct,
+  Indirect,
+  Recursive,
+  Other,
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pin-rm
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PinRmOpts {
+  /// CID or IPFS path
+  pub arg: String,
+  pub recursive: Option<bool>,
+}
+impl_query_string_conversions!("pin/rm?", PinRmOpts);
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pin-update
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PinUpdateOpts {
+  /// pinned CID or IPFS path
+  pub from: String,
+  pub to: String,
+  /// removes the old pin (the node defaults to true)
+  pub unpin: Option<bool>,
+}
+/// a single `arg` parameter, encoded the same way as `impl_query_string_conversions!` fields
+#[derive(Serialize, Deserialize)]
+struct QueryArg {
+  arg: String,
+}
+impl std::fmt::Display for PinUpdateOpts {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let encode = |arg: &str| serde_qs::to_string(&QueryArg { arg: arg.to_string() }).map_err(|_| std::fmt::Error);
+    write!(f, "pin/update?{}&{}", encode(&self.from)?, encode(&self.to)?)?;
+    if let Some(unpin) = self.unpin {
+      write!(f, "&unpin={}", unpin)?;
+    }
+    Ok(())
+  }
+}
+impl FromStr for PinUpdateOpts {
+  type Err = &'static str;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut args = vec![];
+    let mut unpin = None;
+    for param in s.strip_prefix("pin/update?").ok_or("Prefix mismatch")?.split('&') {
+      match param.split_once('=') {
+        Some(("arg", _)) => {
+          let QueryArg { arg } = serde_qs::from_str(param).map_err(|_| "Invalid format")?;
+          args.push(arg);
+        }
+        Some(("unpin", value)) => unpin = Some(value.parse().map_err(|_| "Invalid format")?),
+        _ => return Err("Invalid format"),
+      }
+    }
+
+    if args.len() != 2 {
+      return Err("Invalid number of arguments");
+    }
+
+    Ok(PinUpdateOpts {
+      from: args.remove(0),
+      to: args.remove(0),
+      unpin,
+    })
+  }
+}
+
+// https://ipfs.github.io/pinning-services-api-spec/#tag/pins/paths/~1pins/post
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PinRemoteAddOpts {
+  pub cid: String,
+  pub name: Option<String>,
+  /// multiaddrs of peers providing the content
+  pub origins: Vec<String>,
+}
+
+// https://ipfs.github.io/pinning-services-api-spec/#tag/pins/paths/~1pins/get
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct PinRemoteLsOpts {
+  /// lists only these CIDs (up to 10)
+  pub cid: Vec<String>,
+  pub name: Option<String>,
+  /// the service defaults to `pinned`
+  pub status: Vec<RemotePinStatus>,
+  /// the service defaults to 10
+  pub limit: Option<u64>,
+}
+
+// https://ipfs.github.io/pinning-services-api-spec/#tag/pins/paths/~1pins~1{requestid}/delete
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PinRemoteRmOpts {
+  /// as returned by `PinRemoteAdd` or `PinRemoteLs`
+  pub request_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemotePinStatus {
+  Queued,
+  Pinning,
+  Pinned,
+  Failed,
+}
+
+/// A pin request at the remote pinning service.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RemotePin {
+  pub request_id: String,
+  pub status: RemotePinStatus,
+  /// ISO 8601 timestamp
+  pub created: String,
+  pub cid: String,
+  pub name: Option<String>,
+  /// multiaddrs of the service's peers; connecting to them speeds up the transfer
+  pub delegates: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PinRemoteLsResponse {
+  /// total number of matching pins; `results` is cut at `limit`
+  pub count: u64,
+  pub results: Vec<RemotePin>,
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -298,6 +934,257 @@ mod tests {
     assert_eq!(files_mv.dest, "/test2");
   }
 
+  #[test]
+  fn test_client_config() {
+    assert_eq!(IPFSClient::default().api_url(), "http://127.0.0.1:5001/api/v0");
+    let config = IPFSConfig { api_url: Some("https://ipfs.example.com/node/".into()), ..Default::default() };
+    assert_eq!(IPFSClient::new(config).unwrap().api_url(), "https://ipfs.example.com/node/api/v0");
+    for api_url in ["127.0.0.1:5001", "ftp://example.com"] {
+      let config = IPFSConfig { api_url: Some(api_url.into()), ..Default::default() };
+      assert!(IPFSClient::new(config).unwrap_err().contains(api_url));
+    }
+    let config = IPFSConfig { gateway_url: Some("ipfs://gateway".into()), ..Default::default() };
+    assert!(IPFSClient::new(config).unwrap_err().contains("gateway"));
+    let service = PinningServiceConfig { endpoint: "pin-service.example.com".into(), access_token: "secret".into() };
+    let config = IPFSConfig { pinning_service: Some(service), ..Default::default() };
+    assert!(IPFSClient::new(config).unwrap_err().contains("pinning service"));
+
+    let config: IPFSConfig = serde_json::from_str(
+      r#"{"apiUrl":"https://ipfs.example.com","auth":{"type":"basic","username":"Aladdin","password":"open sesame"},"timeout":5000}"#
+    ).unwrap();
+    assert_eq!(config.timeout, Some(5000));
+    let auth = config.auth.unwrap();
+    assert_eq!(auth.header_value(), "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+    assert!(!format!("{:?}", auth).contains("open sesame"));
+    let auth: IPFSAuth = serde_json::from_str(r#"{"type":"bearer","token":"secret"}"#).unwrap();
+    assert_eq!(auth.header_value(), "Bearer secret");
+    assert!(!format!("{:?}", auth).contains("secret"));
+  }
+
+  #[test]
+  fn test_command_names() {
+    let commands = [
+      IPFSCommand::FilesChCid(FilesChCidOpts::default()),
+      IPFSCommand::FilesLs(FilesLsOpts::default()),
+      IPFSCommand::FilesMv(FilesMvOpts { source: "/a".into(), dest: "/b".into() }),
+      IPFSCommand::FilesRm(FilesRmOpts { arg: "/a".into(), recursive: None, force: None }),
+      IPFSCommand::Version(VersionOpts::default()),
+    ];
+    for command in commands {
+      let query = match &command {
+        IPFSCommand::FilesChCid(opts) => opts.to_string(),
+        IPFSCommand::FilesLs(opts) => opts.to_string(),
+        IPFSCommand::FilesMv(opts) => opts.to_string(),
+        IPFSCommand::FilesRm(opts) => opts.to_string(),
+        IPFSCommand::Version(opts) => opts.to_string(),
+        _ => unreachable!(),
+      };
+      assert_eq!(query.split('?').next(), Some(command.name()));
+    }
+
+    let permissions = crate::permissions::Permissions::parse(&["ipfs://files/ls".to_string()]).unwrap();
+    assert!(IPFSCommand::FilesLs(FilesLsOpts::default()).valid_permissions(&permissions));
+    assert!(!IPFSCommand::Version(VersionOpts::default()).valid_permissions(&permissions));
+  }
+
+  #[test]
+  fn test_immutable_commands_to_query_string() {
+    let add = AddOpts { cid_version: Some(1), raw_leaves: Some(true), wrap_with_directory: Some(true), ..Default::default() };
+    assert_eq!(add.to_string(), "add?cid-version=1&raw-leaves=true&wrap-with-directory=true");
+    assert_eq!(AddOpts::default().to_string(), "add?");
+
+    let cid = "bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy";
+    let cat = CatOpts { arg: cid.into(), offset: Some(6), length: Some(5) };
+    assert_eq!(cat.to_string(), format!("cat?arg={}&offset=6&length=5", cid));
+    let get = GetOpts { arg: format!("/ipfs/{}", cid), archive: Some(true), compress: None, compression_level: None };
+    assert_eq!(get.to_string(), format!("get?arg=%2Fipfs%2F{}&archive=true", cid));
+    let ls = LsOpts { arg: cid.into(), resolve_type: Some(false), size: None };
+    assert_eq!(ls.to_string(), format!("ls?arg={}&resolve-type=false", cid));
+    assert_eq!(LsOpts::from_str(&ls.to_string()).unwrap().resolve_type, Some(false));
+  }
+
+  #[test]
+  fn test_pin_commands_to_query_string() {
+    let cid = "bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy";
+    let add = PinAddOpts { arg: cid.into(), recursive: Some(false), name: Some("backup".into()) };
+    assert_eq!(add.to_string(), format!("pin/add?arg={}&recursive=false&name=backup", cid));
+    let rm = PinRmOpts { arg: format!("/ipfs/{}", cid), recursive: None };
+    assert_eq!(rm.to_string(), format!("pin/rm?arg=%2Fipfs%2F{}", cid));
+    assert_eq!(PinLsOpts::default().to_string(), "pin/ls?");
+    let ls = PinLsOpts { pin_type: Some("recursive".into()), names: Some(true), ..Default::default() };
+    assert_eq!(ls.to_string(), "pin/ls?type=recursive&names=true");
+
+    let mut update = PinUpdateOpts { from: "bafyold".into(), to: "bafynew".into(), unpin: None };
+    assert_eq!(update.to_string(), "pin/update?arg=bafyold&arg=bafynew");
+    update.unpin = Some(false);
+    assert_eq!(update.to_string(), "pin/update?arg=bafyold&arg=bafynew&unpin=false");
+    let update = PinUpdateOpts::from_str(&update.to_string()).unwrap();
+    assert_eq!((update.from.as_str(), update.to.as_str(), update.unpin), ("bafyold", "bafynew", Some(false)));
+    assert!(PinUpdateOpts::from_str("pin/update?arg=bafyold").is_err());
+    assert!(PinUpdateOpts::from_str("pin/update?arg=bafyold&arg=bafynew&unpin=maybe").is_err());
+    // reserved characters in a path must not add RPC parameters
+    let update = PinUpdateOpts { from: "/ipfs/bafyold/a&unpin=true".into(), to: "/ipfs/bafynew#b".into(), unpin: None };
+    assert_eq!(update.to_string(), "pin/update?arg=%2Fipfs%2Fbafyold%2Fa%26unpin%3Dtrue&arg=%2Fipfs%2Fbafynew%23b");
+    let parsed = PinUpdateOpts::from_str(&update.to_string()).unwrap();
+    assert_eq!((parsed.from, parsed.to, parsed.unpin), (update.from, update.to, None));
+
+    let remote = IPFSCommand::PinRemoteLs(PinRemoteLsOpts::default());
+    let permissions = crate::permissions::Permissions::parse(&["ipfs://pin/remote".to_string()]).unwrap();
+    assert_eq!(remote.name(), "pin/remote/ls");
+    assert!(remote.valid_permissions(&permissions));
+    assert!(!IPFSCommand::PinLs(PinLsOpts::default()).valid_permissions(&permissions));
+  }
+
+  #[test]
+  fn test_parse_responses() {
+    let ls: kubo::FilesLs = serde_json::from_str(
+      r#"{"Entries":[{"Name":"test.txt","Type":0,"Size":12,"Hash":"QmTest"},{"Name":"dir","Type":1,"Size":0,"Hash":""}]}"#
+    ).unwrap();
+    assert_eq!(FilesLsResponse::from(ls).entries, vec![
+      FilesEntry { name: "test.txt".into(), entry_type: IPFSEntryType::File, size: 12, hash: Some("QmTest".into()) },
+      FilesEntry { name: "dir".into(), entry_type: IPFSEntryType::Directory, size: 0, hash: None },
+    ]);
+    let ls: kubo::FilesLs = serde_json::from_str(r#"{"Entries":null}"#).unwrap();
+    assert!(FilesLsResponse::from(ls).entries.is_empty());
+
+    let stat: kubo::FilesStat = serde_json::from_str(
+      r#"{"Hash":"QmStat","Size":12,"CumulativeSize":20,"Blocks":1,"Type":"file","WithLocality":false,"Local":false,"SizeLocal":0}"#
+    ).unwrap();
+    let stat = FilesStatResponse::from(stat);
+    assert_eq!((stat.hash.as_str(), stat.size, stat.cumulative_size), ("QmStat", 12, 20));
+    assert_eq!((stat.entry_type, stat.local), (IPFSEntryType::File, None));
+
+    let version: kubo::Version = serde_json::from_str(
+      r#"{"Version":"0.22.0","Commit":"","Repo":"14","System":"amd64/linux","Golang":"go1.19.12"}"#
+    ).unwrap();
+    let version = VersionResponse::from(version);
+    assert_eq!((version.version.as_str(), version.commit), ("0.22.0", None));
+
+    let added = kubo::parse_add(concat!(
+      "{\"Name\":\"a.txt\",\"Bytes\":12}\n",
+      "{\"Name\":\"a.txt\",\"Hash\":\"bafkrei\",\"Size\":\"12\"}\n",
+      "{\"Name\":\"\",\"Hash\":\"bafybei\",\"Size\":\"64\"}\n",
+    ).as_bytes()).unwrap();
+    assert_eq!(added, vec![
+      AddResponse { name: "a.txt".into(), hash: "bafkrei".into(), size: 12 },
+      AddResponse { name: "".into(), hash: "bafybei".into(), size: 64 },
+    ]);
+    assert!(kubo::parse_add(b"not json").is_err());
+
+    let ls: kubo::Ls = serde_json::from_str(
+      r#"{"Objects":[{"Hash":"bafybei","Links":[{"Name":"a.txt","Hash":"bafkrei","Size":12,"Type":2,"Target":""}]}]}"#
+    ).unwrap();
+    let link = &LsResponse::from(ls).objects[0].links[0];
+    assert_eq!((link.name.as_str(), link.entry_type, link.target.as_deref()), ("a.txt", IPFSEntryType::File, None));
+
+    let pins: kubo::Pins = serde_json::from_str(r#"{"Pins":["bafyold","bafynew"],"Progress":0}"#).unwrap();
+    assert_eq!(PinResponse::from(pins).pins, vec!["bafyold", "bafynew"]);
+    let pins: kubo::Pins = serde_json::from_str(r#"{"Pins":null}"#).unwrap();
+    assert!(PinResponse::from(pins).pins.is_empty());
+
+    let ls: kubo::PinLs = serde_json::from_str(
+      r#"{"Keys":{"bafyb":{"Type":"indirect through bafya","Name":""},"bafya":{"Type":"recursive","Name":"backup"}}}"#
+    ).unwrap();
+    assert_eq!(PinLsResponse::from(ls).pins, vec![
+      Pin { cid: "bafya".into(), pin_type: PinType::Recursive, name: Some("backup".into()) },
+      Pin { cid: "bafyb".into(), pin_type: PinType::Indirect, name: None },
+    ]);
+  }
+
+  #[tokio::test]
+  async fn test_gateway_rejects_rpc_commands() {
+    let config = IPFSConfig { gateway_url: Some("https://trustless-gateway.link".into()), ..Default::default() };
+    let client = IPFSClient::new(config).unwrap();
+    let err = IPFSCommand::FilesLs(FilesLsOpts::default()).exec(&client).await.unwrap_err();
+    assert!(err.contains("files/ls"), "{}", err);
+    let cat = CatOpts { arg: "/ipns/example.com".into(), offset: None, length: None };
+    assert!(IPFSCommand::Cat(cat).exec(&client).await.unwrap_err().contains("/ipns/example.com"));
+    // remote pins don't need the node
+    let rm = IPFSCommand::PinRemoteRm(PinRemoteRmOpts { request_id: "id".into() });
+    assert_eq!(rm.exec(&client).await.unwrap_err(), "no remote pinning service configured");
+  }
+
+  #[tokio::test]
+  async fn test_add_rejects_directories() {
+    let client = IPFSClient::default();
+    let add = |names: &[&str]| IPFSCommand::Add(
+      AddOpts::default(),
+      names.iter().map(|name| AddFile { name: name.to_string(), data: vec![] }).collect(),
+    );
+    assert_eq!(add(&[]).exec(&client).await.unwrap_err(), "no files to add");
+    assert!(add(&["a.txt", "dir/b.txt"]).exec(&client).await.unwrap_err().contains("dir/b.txt"));
+  }
+
+  #[tokio::test]
+  async fn test_gateway_requests_carry_no_node_credentials() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (requests, mut received) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+      while let Ok((mut socket, _)) = listener.accept().await {
+        let mut buf = [0u8; 4096];
+        let n = socket.read(&mut buf).await.unwrap();
+        requests.send(String::from_utf8_lossy(&buf[..n]).to_lowercase()).unwrap();
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        socket.write_all(response.as_bytes()).await.unwrap();
+      }
+    });
+
+    let node_auth = IPFSAuth::Bearer { token: "node-secret".into() };
+    let gateway_url = format!("http://{}", addr);
+    let config = IPFSConfig { auth: Some(node_auth.clone()), gateway_url: Some(gateway_url.clone()), ..Default::default() };
+    let client = IPFSClient::new(config).unwrap();
+    client.get("cat", &format!("{}/ipfs/bafkrei", gateway_url), "application/vnd.ipld.raw").await.unwrap();
+    let request = received.recv().await.unwrap();
+    assert!(!request.contains("authorization"), "{}", request);
+
+    let gateway_auth = IPFSAuth::Bearer { token: "gateway-secret".into() };
+    let config = IPFSConfig {
+      auth: Some(node_auth),
+      gateway_url: Some(gateway_url.clone()),
+      gateway_auth: Some(gateway_auth),
+      ..Default::default()
+    };
+    let client = IPFSClient::new(config).unwrap();
+    client.get("cat", &format!("{}/ipfs/bafkrei", gateway_url), "application/vnd.ipld.raw").await.unwrap();
+    let request = received.recv().await.unwrap();
+    assert!(request.contains("authorization: bearer gateway-secret"), "{}", request);
+    assert!(!request.contains("node-secret"), "{}", request);
+  }
+
+  #[tokio::test]
+  async fn test_attempts_are_reported() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::retry::RetryPolicy;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      while let Ok((mut socket, _)) = listener.accept().await {
+        let mut buf = [0u8; 1024];
+        let _ = socket.read(&mut buf).await.unwrap();
+        let response = "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        socket.write_all(response.as_bytes()).await.unwrap();
+      }
+    });
+
+    let config = IPFSConfig { api_url: Some(format!("http://{}", addr)), ..Default::default() };
+    let retry = RetryPolicy { max_attempts: 3, initial_backoff: 1, jitter: false, ..Default::default() };
+    let client = IPFSClient::new(config).unwrap().with_retry(retry);
+    let version = IPFSCommand::Version(VersionOpts::default());
+    let response = version.exec_counted(&client).await;
+    assert_eq!(response.result.unwrap_err(), "Error post response: 503");
+    assert_eq!(response.attempts, 3);
+    // every call is counted on its own
+    assert_eq!(version.exec_counted(&client).await.attempts, 3);
+    assert_eq!(client.attempts(), 0);
+
+    let rejected = IPFSCommand::Add(AddOpts::default(), vec![]).exec_counted(&client).await;
+    assert_eq!(rejected.attempts, 0);
+  }
+
   #[tokio::test]
   async fn test_files_commands_local_node() {
     // VERSION
@@ -413,7 +1300,89 @@ mod tests {
     let files_ls = FilesLsOpts::default();
     assert_eq!(files_ls.to_string(), "files/ls?arg=%2F");
     let res = IPFSCommand::FilesLs(files_ls).exec(&client).await.unwrap();
-    let res_str = String::from_utf8(res).unwrap();
-    assert_eq!(res_str, "{\"Entries\":[{\"Name\":\"test.txt\",\"Type\":0,\"Size\":0,\"Hash\":\"\"}]}\n");
+    let res: FilesLsResponse = serde_json::from_slice(&res).unwrap();
+    assert_eq!(res.entries, vec![FilesEntry { name: "test.txt".into(), entry_type: IPFSEntryType::File, size: 0, hash: None }]);
+  }
+
+  #[tokio::test]
+  async fn test_immutable_commands_local_node() {
+    // ADD
+    // curl -X POST -F file=@test.txt "http://127.0.0.1:5001/api/v0/add?cid-version=1&wrap-with-directory=true"
+
+    // CAT
+    // curl -X POST "http://127.0.0.1:5001/api/v0/cat?arg=<cid>/test.txt&offset=6"
+
+    // LS
+    // curl -X POST "http://127.0.0.1:5001/api/v0/ls?arg=<cid>"
+
+    // GET
+    // curl -X POST "http://127.0.0.1:5001/api/v0/get?arg=<cid>" > dir.tar
+
+    let client = IPFSClient::default();
+
+    let add = AddOpts { cid_version: Some(1), wrap_with_directory: Some(true), ..Default::default() };
+    let file = AddFile { name: "test.txt".into(), data: "hello world!".as_bytes().to_vec() };
+    let res = IPFSCommand::Add(add, vec![file]).exec(&client).await.unwrap();
+    // the wrapping directory is listed last
+    let added: Vec<AddResponse> = serde_json::from_slice(&res).unwrap();
+    assert_eq!(added.len(), 2);
+    assert_eq!(added[0].name, "test.txt");
+    let dir = added[1].hash.clone();
+
+    let cat = CatOpts { arg: format!("{}/test.txt", dir), offset: Some(6), length: None };
+    let res = IPFSCommand::Cat(cat).exec(&client).await.unwrap();
+    assert_eq!(res, b"world!");
+
+    let ls = LsOpts { arg: dir.clone(), resolve_type: None, size: None };
+    let res: LsResponse = serde_json::from_slice(&IPFSCommand::Ls(ls).exec(&client).await.unwrap()).unwrap();
+    assert_eq!(res.objects[0].links[0].name, "test.txt");
+
+    let get = GetOpts { arg: dir, archive: None, compress: None, compression_level: None };
+    let res = IPFSCommand::Get(get).exec(&client).await.unwrap();
+    // tar archives consist of 512 byte blocks
+    assert!(!res.is_empty() && res.len() % 512 == 0);
   }
-}
\ No newline at end of file
+
+  #[tokio::test]
+  async fn test_pin_commands_local_node() {
+    // PIN ADD
+    // curl -X POST "http://127.0.0.1:5001/api/v0/pin/add?arg=<cid>&name=test"
+
+    // PIN LS
+    // curl -X POST "http://127.0.0.1:5001/api/v0/pin/ls?type=recursive&names=true"
+
+    // PIN UPDATE
+    // curl -X POST "http://127.0.0.1:5001/api/v0/pin/update?arg=<cid>&arg=<cid2>"
+
+    // PIN RM
+    // curl -X POST "http://127.0.0.1:5001/api/v0/pin/rm?arg=<cid2>"
+
+    let client = IPFSClient::default();
+
+    let add = |content: &str| {
+      let opts = AddOpts { cid_version: Some(1), pin: Some(false), ..Default::default() };
+      let file = AddFile { name: "pin.txt".into(), data: content.as_bytes().to_vec() };
+      IPFSCommand::Add(opts, vec![file])
+    };
+    let added: Vec<AddResponse> = serde_json::from_slice(&add("pin me").exec(&client).await.unwrap()).unwrap();
+    let cid = added[0].hash.clone();
+    let added: Vec<AddResponse> = serde_json::from_slice(&add("pin me too").exec(&client).await.unwrap()).unwrap();
+    let cid2 = added[0].hash.clone();
+
+    let pin_add = PinAddOpts { arg: cid.clone(), recursive: None, name: Some("test".into()) };
+    let res: PinResponse = serde_json::from_slice(&IPFSCommand::PinAdd(pin_add).exec(&client).await.unwrap()).unwrap();
+    assert_eq!(res.pins, vec![cid.clone()]);
+
+    let pin_ls = PinLsOpts { pin_type: Some("recursive".into()), names: Some(true), ..Default::default() };
+    let res: PinLsResponse = serde_json::from_slice(&IPFSCommand::PinLs(pin_ls).exec(&client).await.unwrap()).unwrap();
+    assert!(res.pins.contains(&Pin { cid: cid.clone(), pin_type: PinType::Recursive, name: Some("test".into()) }));
+
+    let pin_update = PinUpdateOpts { from: cid.clone(), to: cid2.clone(), unpin: None };
+    let res: PinResponse = serde_json::from_slice(&IPFSCommand::PinUpdate(pin_update).exec(&client).await.unwrap()).unwrap();
+    assert_eq!(res.pins, vec![cid, cid2.clone()]);
+
+    let pin_rm = PinRmOpts { arg: cid2.clone(), recursive: None };
+    let res: PinResponse = serde_json::from_slice(&IPFSCommand::PinRm(pin_rm).exec(&client).await.unwrap()).unwrap();
+    assert_eq!(res.pins, vec![cid2]);
+  }
+}
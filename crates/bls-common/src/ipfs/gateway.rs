@@ -0,0 +1,691 @@
+//! Trustless gateway backend: content is fetched as raw blocks (`?format=raw`) or CAR files (`?format=car`)
+//! and every block is checked against its CID before anything is handed to the guest.
+//! See https://specs.ipfs.tech/http-gateways/trustless-gateway/
+use std::collections::HashMap;
+use std::fmt;
+use sha2::{Digest, Sha256};
+use super::client::IPFSClient;
+use super::{CatOpts, GetOpts, IPFSCommand};
+
+const RAW: u64 = 0x55;
+const DAG_PB: u64 = 0x70;
+const IDENTITY: u64 = 0x00;
+const SHA2_256: u64 = 0x12;
+
+/// Upper bound of the content (or archive) assembled for a single `cat` or `get`.
+const MAX_OUTPUT_BYTES: usize = 64 << 20;
+/// Upper bound of the nodes visited for a single `cat` or `get`, counting every visit of a shared block.
+const MAX_VISITED_NODES: usize = 100_000;
+
+const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Serves the commands that read immutable content; everything else needs the RPC API.
+pub(super) async fn exec(command: &IPFSCommand, client: &IPFSClient) -> Result<Vec<u8>, String> {
+  match command {
+    IPFSCommand::Cat(opts) => cat(client, opts).await,
+    IPFSCommand::Get(opts) => get(client, opts).await,
+    _ => Err(format!("ipfs command `{}` is not supported by the gateway backend", command.name())),
+  }
+}
+
+async fn cat(client: &IPFSClient, opts: &CatOpts) -> Result<Vec<u8>, String> {
+  let (root, path) = parse_path(&opts.arg)?;
+  let dag = fetch(client, "cat", &root, &path).await?;
+  let content = dag.read(&dag.resolve(&root, &path)?, &mut Budget::default())?;
+  let offset = (opts.offset.unwrap_or(0) as usize).min(content.len());
+  let end = match opts.length {
+    Some(length) => offset.saturating_add(length as usize).min(content.len()),
+    None => content.len(),
+  };
+  Ok(content[offset..end].to_vec())
+}
+
+async fn get(client: &IPFSClient, opts: &GetOpts) -> Result<Vec<u8>, String> {
+  if opts.compress == Some(true) {
+    return Err("compressed archives are not supported by the gateway backend".into());
+  }
+  let (root, path) = parse_path(&opts.arg)?;
+  let dag = fetch(client, "get", &root, &path).await?;
+  // like the RPC API: the archive is rooted at the last path segment (or the CID)
+  let name = path.last().cloned().unwrap_or_else(|| root.to_string());
+  let mut archive = vec![];
+  dag.tar(&dag.resolve(&root, &path)?, &name, &mut archive, &mut Budget::default())?;
+  archive.extend_from_slice(&[0; 1024]);
+  Ok(archive)
+}
+
+/// Fetches the blocks of `root/path` from the gateway; a raw block is fetched as is, anything else as CAR.
+async fn fetch(client: &IPFSClient, command: &str, root: &Cid, path: &[String]) -> Result<Dag, String> {
+  let gateway = client.gateway_url().ok_or("no gateway configured")?;
+  let url = format!("{}/ipfs/{}", gateway.as_str().trim_end_matches('/'), root);
+  if root.codec == RAW && path.is_empty() {
+    let block = client.get(command, &format!("{}?format=raw", url), "application/vnd.ipld.raw").await?;
+    root.verify(&block)?;
+    return Ok(Dag { blocks: HashMap::from([(root.clone(), block)]) });
+  }
+  let path: String = path.iter().map(|segment| format!("/{}", encode_segment(segment))).collect();
+  let car = client.get(command, &format!("{}{}?format=car", url, path), "application/vnd.ipld.car").await?;
+  Ok(Dag { blocks: parse_car(&car)? })
+}
+
+/// Splits `<cid>[/path]` or `/ipfs/<cid>[/path]` into the root CID and the path segments.
+fn parse_path(arg: &str) -> Result<(Cid, Vec<String>), String> {
+  let arg = arg.trim();
+  let rest = match arg.strip_prefix('/') {
+    Some(rest) => rest.strip_prefix("ipfs/").ok_or_else(|| format!("only /ipfs paths can be verified: \"{}\"", arg))?,
+    None => arg,
+  };
+  let mut segments = rest.split('/').filter(|segment| !segment.is_empty());
+  let root = Cid::parse(segments.next().unwrap_or_default())?;
+  Ok((root, segments.map(str::to_string).collect()))
+}
+
+fn encode_segment(segment: &str) -> String {
+  segment
+    .bytes()
+    .map(|byte| match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+      _ => format!("%{:02X}", byte),
+    })
+    .collect()
+}
+
+/// A content identifier (CIDv0 or CIDv1).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cid {
+  pub version: u64,
+  pub codec: u64,
+  pub hash_code: u64,
+  pub digest: Vec<u8>,
+}
+
+impl Cid {
+  /// Parses a CIDv0 (`Qm...`) or a base32 (`b...`), base58btc (`z...`) or base16 (`f...`) CIDv1.
+  pub fn parse(cid: &str) -> Result<Cid, String> {
+    let invalid = |reason: &str| format!("invalid cid \"{}\": {}", cid, reason);
+    let bytes = if cid.len() == 46 && cid.starts_with("Qm") {
+      base58_decode(cid)
+    } else {
+      match cid.chars().next() {
+        Some('b') => base32_decode(&cid[1..]),
+        Some('B') => base32_decode(&cid[1..].to_ascii_lowercase()),
+        Some('z') => base58_decode(&cid[1..]),
+        Some('f') => base16_decode(&cid[1..]),
+        _ => None,
+      }
+    };
+    let bytes = bytes.ok_or_else(|| invalid("unsupported or malformed encoding"))?;
+    match Cid::from_bytes(&bytes) {
+      Ok((cid, len)) if len == bytes.len() => Ok(cid),
+      Ok(_) => Err(invalid("trailing bytes")),
+      Err(e) => Err(invalid(&e)),
+    }
+  }
+
+  /// Reads a binary CID; responds with the CID and the number of bytes read.
+  pub fn from_bytes(bytes: &[u8]) -> Result<(Cid, usize), String> {
+    if bytes.starts_with(&[0x12, 0x20]) {
+      let digest = bytes.get(2..34).ok_or("truncated cid")?;
+      return Ok((Cid { version: 0, codec: DAG_PB, hash_code: SHA2_256, digest: digest.to_vec() }, 34));
+    }
+    let mut pos = 0;
+    let version = read_varint(bytes, &mut pos)?;
+    if version != 1 {
+      return Err(format!("unsupported cid version {}", version));
+    }
+    let codec = read_varint(bytes, &mut pos)?;
+    let hash_code = read_varint(bytes, &mut pos)?;
+    let len = read_varint(bytes, &mut pos)? as usize;
+    let digest = pos.checked_add(len).and_then(|end| bytes.get(pos..end)).ok_or("truncated cid")?;
+    Ok((Cid { version, codec, hash_code, digest: digest.to_vec() }, pos + len))
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = vec![];
+    if self.version != 0 {
+      write_varint(&mut bytes, self.version);
+      write_varint(&mut bytes, self.codec);
+    }
+    write_varint(&mut bytes, self.hash_code);
+    write_varint(&mut bytes, self.digest.len() as u64);
+    bytes.extend_from_slice(&self.digest);
+    bytes
+  }
+
+  /// Checks that the block hashes to this CID.
+  pub fn verify(&self, block: &[u8]) -> Result<(), String> {
+    let matches = match self.hash_code {
+      SHA2_256 => Sha256::digest(block).as_slice() == self.digest,
+      IDENTITY => block == self.digest,
+      code => return Err(format!("unsupported hash function 0x{:x} of {}", code, self)),
+    };
+    match matches {
+      true => Ok(()),
+      false => Err(format!("block {} does not match its hash", self)),
+    }
+  }
+}
+
+impl fmt::Display for Cid {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.version {
+      0 => write!(f, "{}", base58_encode(&self.to_bytes())),
+      _ => write!(f, "b{}", base32_encode(&self.to_bytes())),
+    }
+  }
+}
+
+/// Verified blocks by CID.
+struct Dag {
+  blocks: HashMap<Cid, Vec<u8>>,
+}
+
+/// A decoded UnixFS node.
+enum Node {
+  /// content of a `raw` block or a single-block UnixFS raw node
+  Raw(Vec<u8>),
+  File { data: Vec<u8>, children: Vec<Cid> },
+  Directory { entries: Vec<(String, Cid)> },
+  Symlink(String),
+}
+
+/// Remaining work for reading a DAG: a small DAG can link the same blocks many times per level,
+/// so its content may grow exponentially with its depth.
+struct Budget {
+  bytes: usize,
+  nodes: usize,
+}
+
+impl Default for Budget {
+  fn default() -> Self {
+    Budget { bytes: MAX_OUTPUT_BYTES, nodes: MAX_VISITED_NODES }
+  }
+}
+
+impl Budget {
+  fn visit(&mut self) -> Result<(), String> {
+    self.nodes = self.nodes.checked_sub(1).ok_or("content links too many blocks")?;
+    Ok(())
+  }
+
+  fn output(&mut self, len: usize) -> Result<(), String> {
+    self.bytes = self.bytes.checked_sub(len).ok_or("content exceeds the size limit of the gateway backend")?;
+    Ok(())
+  }
+}
+
+impl Dag {
+  fn node(&self, cid: &Cid) -> Result<Node, String> {
+    // identity CIDs carry their content and are never sent as blocks
+    let block = match cid.hash_code {
+      IDENTITY => &cid.digest,
+      _ => self.blocks.get(cid).ok_or_else(|| format!("gateway response is missing block {}", cid))?,
+    };
+    match cid.codec {
+      RAW => Ok(Node::Raw(block.clone())),
+      DAG_PB => decode_unixfs(block).map_err(|e| format!("invalid block {}: {}", cid, e)),
+      codec => Err(format!("unsupported codec 0x{:x} of {}", codec, cid)),
+    }
+  }
+
+  /// Follows the path through the directories below `root`.
+  fn resolve(&self, root: &Cid, path: &[String]) -> Result<Cid, String> {
+    let mut cid = root.clone();
+    for segment in path {
+      cid = match self.node(&cid)? {
+        Node::Directory { entries } => entries
+          .into_iter()
+          .find(|(name, _)| name == segment)
+          .map(|(_, cid)| cid)
+          .ok_or_else(|| format!("no link named \"{}\" under {}", segment, cid))?,
+        _ => return Err(format!("{} is not a directory", cid)),
+      };
+    }
+    Ok(cid)
+  }
+
+  /// Reads the content of a file.
+  fn read(&self, cid: &Cid, budget: &mut Budget) -> Result<Vec<u8>, String> {
+    let mut content = vec![];
+    let mut pending = vec![cid.clone()];
+    while let Some(cid) = pending.pop() {
+      budget.visit()?;
+      match self.node(&cid)? {
+        Node::Raw(data) => {
+          budget.output(data.len())?;
+          content.extend_from_slice(&data);
+        }
+        Node::File { data, children } => {
+          budget.output(data.len())?;
+          content.extend_from_slice(&data);
+          // depth-first, in link order
+          pending.extend(children.into_iter().rev());
+        }
+        Node::Directory { .. } => return Err(format!("{} is a directory", cid)),
+        Node::Symlink(_) => return Err(format!("{} is a symlink", cid)),
+      }
+    }
+    Ok(content)
+  }
+
+  /// Appends the file or directory as tar entries named `name`.
+  fn tar(&self, cid: &Cid, name: &str, archive: &mut Vec<u8>, budget: &mut Budget) -> Result<(), String> {
+    match self.node(cid)? {
+      Node::Directory { entries } => {
+        budget.visit()?;
+        budget.output(512)?;
+        archive.extend_from_slice(&tar_header(&format!("{}/", name), 0, b'5', "")?);
+        for (entry, child) in entries {
+          // like Kubo: entries must not escape the directory or hide in the name
+          if entry.is_empty() || entry == "." || entry == ".." || entry.contains(['/', '\0']) {
+            return Err(format!("invalid entry name {:?} in directory {}", entry, cid));
+          }
+          self.tar(&child, &format!("{}/{}", name, entry), archive, budget)?;
+        }
+      }
+      Node::Symlink(target) => {
+        budget.visit()?;
+        budget.output(512)?;
+        archive.extend_from_slice(&tar_header(name, 0, b'2', &target)?);
+      }
+      Node::Raw(_) | Node::File { .. } => {
+        let content = self.read(cid, budget)?;
+        budget.output(512)?;
+        archive.extend_from_slice(&tar_header(name, content.len(), b'0', "")?);
+        archive.extend_from_slice(&content);
+        archive.resize(archive.len().next_multiple_of(512), 0);
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Reads the blocks of a CARv1 file, verifying each of them; the roots in the header are not trusted.
+fn parse_car(car: &[u8]) -> Result<HashMap<Cid, Vec<u8>>, String> {
+  // CARv2 starts with a fixed pragma (a header announcing version 2)
+  if car.starts_with(&[0x0a, 0xa1, 0x67, b'v', b'e', b'r', b's', b'i', b'o', b'n', 0x02]) {
+    return Err("CARv2 responses are not supported".into());
+  }
+  let mut pos = 0;
+  let header = read_varint(car, &mut pos)? as usize;
+  pos = pos.checked_add(header).filter(|&pos| pos <= car.len()).ok_or("truncated car header")?;
+
+  let mut blocks = HashMap::new();
+  while pos < car.len() {
+    let len = read_varint(car, &mut pos)? as usize;
+    let section = pos.checked_add(len).and_then(|end| car.get(pos..end)).ok_or("truncated car section")?;
+    pos += len;
+    let (cid, cid_len) = Cid::from_bytes(section)?;
+    let block = &section[cid_len..];
+    cid.verify(block)?;
+    blocks.insert(cid, block.to_vec());
+  }
+  Ok(blocks)
+}
+
+/// Decodes a dag-pb node holding UnixFS data.
+fn decode_unixfs(block: &[u8]) -> Result<Node, String> {
+  // PBNode: 1 = Data, 2 = Links; PBLink: 1 = Hash, 2 = Name
+  let mut data = None;
+  let mut links = vec![];
+  for (field, value) in protobuf_fields(block)? {
+    match (field, value) {
+      (1, Field::Bytes(bytes)) => data = Some(bytes),
+      (2, Field::Bytes(link)) => {
+        let mut hash = None;
+        let mut name = String::new();
+        for (field, value) in protobuf_fields(link)? {
+          match (field, value) {
+            (1, Field::Bytes(bytes)) => hash = Some(Cid::from_bytes(bytes)?.0),
+            (2, Field::Bytes(bytes)) => name = String::from_utf8(bytes.to_vec()).map_err(|_| "invalid link name")?,
+            _ => {}
+          }
+        }
+        links.push((name, hash.ok_or("link without hash")?));
+      }
+      _ => {}
+    }
+  }
+
+  // UnixFS Data: 1 = Type, 2 = Data
+  let mut unixfs_type = None;
+  let mut content = vec![];
+  for (field, value) in protobuf_fields(data.ok_or("missing unixfs data")?)? {
+    match (field, value) {
+      (1, Field::Varint(value)) => unixfs_type = Some(value),
+      (2, Field::Bytes(bytes)) => content = bytes.to_vec(),
+      _ => {}
+    }
+  }
+  match unixfs_type {
+    Some(0) => Ok(Node::Raw(content)),
+    Some(1) => Ok(Node::Directory { entries: links }),
+    Some(2) => Ok(Node::File { data: content, children: links.into_iter().map(|(_, cid)| cid).collect() }),
+    Some(4) => Ok(Node::Symlink(String::from_utf8(content).map_err(|_| "invalid symlink target")?)),
+    Some(5) => Err("sharded directories are not supported".into()),
+    _ => Err("unsupported unixfs type".into()),
+  }
+}
+
+enum Field<'a> {
+  Varint(u64),
+  Bytes(&'a [u8]),
+  Fixed,
+}
+
+fn protobuf_fields(mut bytes: &[u8]) -> Result<Vec<(u64, Field<'_>)>, String> {
+  let mut fields = vec![];
+  while !bytes.is_empty() {
+    let mut pos = 0;
+    let key = read_varint(bytes, &mut pos)?;
+    let value = match key & 7 {
+      0 => Field::Varint(read_varint(bytes, &mut pos)?),
+      1 | 5 => {
+        pos += if key & 7 == 1 { 8 } else { 4 };
+        Field::Fixed
+      }
+      2 => {
+        let len = read_varint(bytes, &mut pos)? as usize;
+        let value = pos.checked_add(len).and_then(|end| bytes.get(pos..end)).ok_or("truncated protobuf field")?;
+        pos += len;
+        Field::Bytes(value)
+      }
+      _ => return Err("unsupported protobuf wire type".into()),
+    };
+    bytes = bytes.get(pos..).ok_or("truncated protobuf field")?;
+    fields.push((key >> 3, value));
+  }
+  Ok(fields)
+}
+
+/// A ustar header; names longer than 100 bytes are split into prefix and name.
+fn tar_header(path: &str, size: usize, kind: u8, link: &str) -> Result<[u8; 512], String> {
+  let (prefix, name) = match path.len() {
+    0..=100 => ("", path),
+    _ => path
+      .char_indices()
+      .filter(|(_, c)| *c == '/')
+      .map(|(idx, _)| (&path[..idx], &path[idx + 1..]))
+      .find(|(prefix, name)| prefix.len() <= 155 && name.len() <= 100 && !name.is_empty())
+      .ok_or_else(|| format!("path too long for a tar archive: \"{}\"", path))?,
+  };
+  if link.len() > 100 {
+    return Err(format!("symlink target too long for a tar archive: \"{}\"", link));
+  }
+
+  let mut header = [0u8; 512];
+  let mode = if kind == b'5' { 0o755 } else { 0o644 };
+  header[..name.len()].copy_from_slice(name.as_bytes());
+  header[100..108].copy_from_slice(format!("{:07o}\0", mode).as_bytes());
+  header[108..116].copy_from_slice(b"0000000\0");
+  header[116..124].copy_from_slice(b"0000000\0");
+  header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+  header[136..148].copy_from_slice(b"00000000000\0");
+  header[148..156].copy_from_slice(b"        ");
+  header[156] = kind;
+  header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+  header[257..263].copy_from_slice(b"ustar\0");
+  header[263..265].copy_from_slice(b"00");
+  header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+  let checksum: u32 = header.iter().map(|byte| *byte as u32).sum();
+  header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+  Ok(header)
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64, String> {
+  let mut value = 0u64;
+  for shift in (0..64).step_by(7) {
+    let byte = *bytes.get(*pos).ok_or("truncated varint")?;
+    *pos += 1;
+    value |= ((byte & 0x7f) as u64) << shift;
+    if byte & 0x80 == 0 {
+      return Ok(value);
+    }
+  }
+  Err("varint overflow".into())
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+  while value >= 0x80 {
+    bytes.push((value as u8) | 0x80);
+    value >>= 7;
+  }
+  bytes.push(value as u8);
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+  let mut encoded = String::new();
+  let (mut buffer, mut bits) = (0u32, 0);
+  for byte in bytes {
+    buffer = (buffer << 8) | *byte as u32;
+    bits += 8;
+    while bits >= 5 {
+      bits -= 5;
+      encoded.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+    }
+  }
+  if bits > 0 {
+    encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+  }
+  encoded
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+  let mut bytes = vec![];
+  let (mut buffer, mut bits) = (0u32, 0);
+  for c in encoded.bytes() {
+    buffer = (buffer << 5) | BASE32_ALPHABET.iter().position(|&a| a == c)? as u32;
+    bits += 5;
+    if bits >= 8 {
+      bits -= 8;
+      bytes.push((buffer >> bits) as u8);
+    }
+  }
+  Some(bytes)
+}
+
+fn base58_encode(bytes: &[u8]) -> String {
+  let mut digits: Vec<u8> = vec![];
+  for byte in bytes {
+    let mut carry = *byte as u32;
+    for digit in digits.iter_mut() {
+      carry += (*digit as u32) << 8;
+      *digit = (carry % 58) as u8;
+      carry /= 58;
+    }
+    while carry > 0 {
+      digits.push((carry % 58) as u8);
+      carry /= 58;
+    }
+  }
+  let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+  let mut encoded = "1".repeat(zeros);
+  encoded.extend(digits.iter().rev().map(|digit| BASE58_ALPHABET[*digit as usize] as char));
+  encoded
+}
+
+fn base58_decode(encoded: &str) -> Option<Vec<u8>> {
+  let mut bytes: Vec<u8> = vec![];
+  for c in encoded.bytes() {
+    let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+    for byte in bytes.iter_mut() {
+      carry += (*byte as u32) * 58;
+      *byte = carry as u8;
+      carry >>= 8;
+    }
+    while carry > 0 {
+      bytes.push(carry as u8);
+      carry >>= 8;
+    }
+  }
+  let zeros = encoded.bytes().take_while(|c| *c == b'1').count();
+  bytes.resize(bytes.len() + zeros, 0);
+  bytes.reverse();
+  Some(bytes)
+}
+
+fn base16_decode(encoded: &str) -> Option<Vec<u8>> {
+  if encoded.len() % 2 == 1 {
+    return None;
+  }
+  (0..encoded.len()).step_by(2).map(|i| u8::from_str_radix(encoded.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn raw_cid(block: &[u8]) -> Cid {
+    Cid { version: 1, codec: RAW, hash_code: SHA2_256, digest: Sha256::digest(block).to_vec() }
+  }
+
+  fn field(bytes: &mut Vec<u8>, number: u64, value: &[u8]) {
+    write_varint(bytes, number << 3 | 2);
+    write_varint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value);
+  }
+
+  /// A dag-pb block of the UnixFS type with links (name, cid).
+  fn unixfs_block(unixfs_type: u64, data: &[u8], links: &[(&str, &Cid)]) -> (Cid, Vec<u8>) {
+    let mut unixfs = vec![0x08];
+    write_varint(&mut unixfs, unixfs_type);
+    if !data.is_empty() {
+      field(&mut unixfs, 2, data);
+    }
+    let mut block = vec![];
+    for (name, cid) in links {
+      let mut link = vec![];
+      field(&mut link, 1, &cid.to_bytes());
+      field(&mut link, 2, name.as_bytes());
+      field(&mut block, 2, &link);
+    }
+    field(&mut block, 1, &unixfs);
+    let cid = Cid { version: 1, codec: DAG_PB, hash_code: SHA2_256, digest: Sha256::digest(&block).to_vec() };
+    (cid, block)
+  }
+
+  fn car(blocks: &[(&Cid, &[u8])]) -> Vec<u8> {
+    // the header (dag-cbor `{roots, version: 1}`) is skipped, hence any bytes do
+    let mut car = vec![];
+    write_varint(&mut car, 3);
+    car.extend_from_slice(b"hdr");
+    for (cid, block) in blocks {
+      let cid = cid.to_bytes();
+      write_varint(&mut car, (cid.len() + block.len()) as u64);
+      car.extend_from_slice(&cid);
+      car.extend_from_slice(block);
+    }
+    car
+  }
+
+  #[test]
+  fn test_cid_encodings() {
+    let cid = raw_cid(b"hello world");
+    let encoded = cid.to_string();
+    assert!(encoded.starts_with("bafkrei"));
+    assert_eq!(Cid::parse(&encoded).unwrap(), cid);
+    assert_eq!(Cid::parse(&encoded.to_ascii_uppercase()).unwrap(), cid);
+    assert_eq!(Cid::parse(&format!("z{}", base58_encode(&cid.to_bytes()))).unwrap(), cid);
+    let hex: String = cid.to_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    assert_eq!(Cid::parse(&format!("f{}", hex)).unwrap(), cid);
+
+    let v0 = Cid { version: 0, codec: DAG_PB, hash_code: SHA2_256, digest: Sha256::digest(b"node").to_vec() };
+    let encoded = v0.to_string();
+    assert!(encoded.starts_with("Qm") && encoded.len() == 46);
+    assert_eq!(Cid::parse(&encoded).unwrap(), v0);
+
+    for invalid in ["", "Qm0OIl", "xabc", "bafkrei!", &format!("{}aa", cid)] {
+      assert!(Cid::parse(invalid).unwrap_err().contains(invalid));
+    }
+  }
+
+  #[test]
+  fn test_verify_blocks() {
+    let cid = raw_cid(b"hello world");
+    assert!(cid.verify(b"hello world").is_ok());
+    assert!(cid.verify(b"hello world!").unwrap_err().contains("does not match"));
+    let inline = Cid { version: 1, codec: RAW, hash_code: IDENTITY, digest: b"tiny".to_vec() };
+    assert!(inline.verify(b"tiny").is_ok());
+    let blake3 = Cid { hash_code: 0x1e, ..cid.clone() };
+    assert!(blake3.verify(b"hello world").unwrap_err().contains("unsupported hash function"));
+
+    // a tampered block fails the whole car
+    let car_bytes = car(&[(&cid, b"hello world")]);
+    assert!(parse_car(&car_bytes).is_ok());
+    assert!(parse_car(&car(&[(&cid, b"hello there")])).is_err());
+    assert!(parse_car(&car_bytes[..car_bytes.len() - 1]).is_err());
+  }
+
+  #[test]
+  fn test_read_and_archive_dag() {
+    let (first, second) = (b"hello ".as_slice(), b"world".as_slice());
+    let (first_cid, second_cid) = (raw_cid(first), raw_cid(second));
+    let (file_cid, file) = unixfs_block(2, b"", &[("", &first_cid), ("", &second_cid)]);
+    let inline = Cid { version: 1, codec: RAW, hash_code: IDENTITY, digest: b"tiny".to_vec() };
+    let (dir_cid, dir) = unixfs_block(1, b"", &[("hello.txt", &file_cid), ("tiny.txt", &inline)]);
+    let blocks = parse_car(&car(&[(&dir_cid, &dir), (&file_cid, &file), (&first_cid, first), (&second_cid, second)])).unwrap();
+    let dag = Dag { blocks };
+
+    let (root, path) = parse_path(&format!("/ipfs/{}/hello.txt", dir_cid)).unwrap();
+    assert_eq!((root.clone(), path.clone()), (dir_cid.clone(), vec!["hello.txt".to_string()]));
+    assert_eq!(dag.read(&dag.resolve(&root, &path).unwrap(), &mut Budget::default()).unwrap(), b"hello world");
+    assert_eq!(dag.read(&inline, &mut Budget::default()).unwrap(), b"tiny");
+    assert!(dag.resolve(&root, &["missing".into()]).unwrap_err().contains("missing"));
+    assert!(dag.read(&dir_cid, &mut Budget::default()).unwrap_err().contains("is a directory"));
+    assert!(parse_path("/ipns/example.com").is_err());
+
+    let mut archive = vec![];
+    dag.tar(&dir_cid, "dir", &mut archive, &mut Budget::default()).unwrap();
+    // directory header, file header and content block, inline file header and content block
+    assert_eq!(archive.len(), 5 * 512);
+    assert_eq!(&archive[..4], b"dir/");
+    assert_eq!(archive[156], b'5');
+    assert_eq!(&archive[512..525], b"dir/hello.txt");
+    assert_eq!(&archive[512 + 124..512 + 136], b"00000000013\0");
+    assert_eq!(&archive[1024..1035], b"hello world");
+    let checksum: u32 = archive[512..1024].iter().enumerate()
+      .map(|(i, byte)| if (148..156).contains(&i) { b' ' as u32 } else { *byte as u32 })
+      .sum();
+    assert_eq!(&archive[512 + 148..512 + 154], format!("{:06o}", checksum).as_bytes());
+
+    // a missing block is an error, not a short read
+    let dag = Dag { blocks: dag.blocks.into_iter().filter(|(cid, _)| *cid != second_cid).collect() };
+    assert!(dag.read(&file_cid, &mut Budget::default()).unwrap_err().contains("missing block"));
+  }
+
+  #[test]
+  fn test_repeated_links_are_bounded() {
+    // every level links the one below twice: 2^32 copies of the leaf from a handful of blocks
+    let grow = |leaf: &[u8]| {
+      let mut blocks = vec![(raw_cid(leaf), leaf.to_vec())];
+      for _ in 0..32 {
+        let below = blocks.last().unwrap().0.clone();
+        blocks.push(unixfs_block(2, b"", &[("", &below), ("", &below)]));
+      }
+      let root = blocks.last().unwrap().0.clone();
+      (root, Dag { blocks: blocks.into_iter().collect() })
+    };
+    let (root, dag) = grow(b"ab");
+    let budget = &mut Budget { bytes: 1 << 16, nodes: MAX_VISITED_NODES };
+    assert!(dag.read(&root, budget).unwrap_err().contains("size limit"));
+    let (dir, dir_block) = unixfs_block(1, b"", &[("big.bin", &root)]);
+    let dag = Dag { blocks: dag.blocks.into_iter().chain([(dir.clone(), dir_block)]).collect() };
+    let budget = &mut Budget { bytes: 1 << 16, nodes: MAX_VISITED_NODES };
+    assert!(dag.tar(&dir, "dir", &mut vec![], budget).unwrap_err().contains("size limit"));
+    // empty leaves produce no output, the visits are bounded too
+    let (root, dag) = grow(b"");
+    assert!(dag.read(&root, &mut Budget::default()).unwrap_err().contains("too many blocks"));
+  }
+
+  #[test]
+  fn test_archive_rejects_invalid_entry_names() {
+    let file = raw_cid(b"x");
+    for name in ["..", ".", "", "a/b", "a\0b"] {
+      let (dir, dir_block) = unixfs_block(1, b"", &[(name, &file)]);
+      let dag = Dag { blocks: HashMap::from([(dir.clone(), dir_block), (file.clone(), b"x".to_vec())]) };
+      let err = dag.tar(&dir, "dir", &mut vec![], &mut Budget::default()).unwrap_err();
+      assert!(err.contains("invalid entry name"), "{:?}: {}", name, err);
+    }
+  }
+}
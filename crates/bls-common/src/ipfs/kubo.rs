@@ -0,0 +1,271 @@
+//! JSON documents of the Kubo RPC API; converted into the response types handed to the guest.
+use std::collections::BTreeMap;
+use serde::Deserialize;
+use super::{
+  FilesEntry, FilesLsResponse, FilesStatResponse, IPFSEntryType, LsLink, LsObject, LsResponse, Pin,
+  PinLsResponse, PinResponse, PinType, VersionResponse,
+};
+
+/// Kubo omits empty lists or sends `null` in their place.
+fn null_as_empty<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+  D: serde::Deserializer<'de>,
+  T: Deserialize<'de>,
+{
+  Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Empty strings (e.g. the hash of `files/ls` without `long`) are treated as not set.
+fn non_empty(value: Option<String>) -> Option<String> {
+  value.filter(|value| !value.is_empty())
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-files-ls
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct FilesLs {
+  #[serde(default, deserialize_with = "null_as_empty")]
+  entries: Vec<FilesLsEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FilesLsEntry {
+  name: String,
+  #[serde(rename = "Type")]
+  entry_type: i32,
+  #[serde(default)]
+  size: u64,
+  hash: Option<String>,
+}
+
+impl From<FilesLs> for FilesLsResponse {
+  fn from(ls: FilesLs) -> Self {
+    FilesLsResponse {
+      entries: ls
+        .entries
+        .into_iter()
+        .map(|entry| FilesEntry {
+          name: entry.name,
+          // MFS node types: 0 = file, 1 = directory
+          entry_type: match entry.entry_type {
+            0 => IPFSEntryType::File,
+            1 => IPFSEntryType::Directory,
+            _ => IPFSEntryType::Other,
+          },
+          size: entry.size,
+          hash: non_empty(entry.hash),
+        })
+        .collect(),
+    }
+  }
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-files-stat
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct FilesStat {
+  hash: String,
+  #[serde(default)]
+  size: u64,
+  #[serde(default)]
+  cumulative_size: u64,
+  #[serde(default)]
+  blocks: u64,
+  #[serde(rename = "Type")]
+  entry_type: String,
+  with_locality: Option<bool>,
+  local: Option<bool>,
+  size_local: Option<u64>,
+}
+
+impl From<FilesStat> for FilesStatResponse {
+  fn from(stat: FilesStat) -> Self {
+    let with_locality = stat.with_locality.unwrap_or(false);
+    FilesStatResponse {
+      hash: stat.hash,
+      size: stat.size,
+      cumulative_size: stat.cumulative_size,
+      blocks: stat.blocks,
+      entry_type: match stat.entry_type.as_str() {
+        "file" => IPFSEntryType::File,
+        "directory" => IPFSEntryType::Directory,
+        "symlink" => IPFSEntryType::Symlink,
+        _ => IPFSEntryType::Other,
+      },
+      local: stat.local.filter(|_| with_locality),
+      size_local: stat.size_local.filter(|_| with_locality),
+    }
+  }
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-version
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct Version {
+  version: String,
+  commit: Option<String>,
+  repo: Option<String>,
+  system: Option<String>,
+  golang: Option<String>,
+}
+
+impl From<Version> for VersionResponse {
+  fn from(version: Version) -> Self {
+    VersionResponse {
+      version: version.version,
+      commit: non_empty(version.commit),
+      repo: non_empty(version.repo),
+      system: non_empty(version.system),
+      golang: non_empty(version.golang),
+    }
+  }
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-add
+#[cfg(feature = "use-wasm-bindgen")]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Added {
+  name: Option<String>,
+  hash: Option<String>,
+  /// NOTE: sent as string
+  size: Option<serde_json::Value>,
+}
+
+/// Parses the response of `add` - one json object per line; progress lines (without hash) are skipped.
+#[cfg(feature = "use-wasm-bindgen")]
+pub(super) fn parse_add(body: &[u8]) -> Result<Vec<super::AddResponse>, String> {
+  let body = std::str::from_utf8(body).map_err(|_| "invalid add response")?;
+  let mut added = vec![];
+  for line in body.lines().filter(|line| !line.trim().is_empty()) {
+    let line: Added = serde_json::from_str(line).map_err(|e| format!("invalid add response: {}", e))?;
+    let hash = match line.hash {
+      Some(hash) => hash,
+      None => continue,
+    };
+    let size = match line.size {
+      Some(serde_json::Value::String(size)) => size.parse().ok(),
+      Some(serde_json::Value::Number(size)) => size.as_u64(),
+      _ => None,
+    };
+    added.push(super::AddResponse { name: line.name.unwrap_or_default(), hash, size: size.unwrap_or_default() });
+  }
+  Ok(added)
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-ls
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct Ls {
+  #[serde(default, deserialize_with = "null_as_empty")]
+  objects: Vec<Object>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Object {
+  hash: String,
+  #[serde(default, deserialize_with = "null_as_empty")]
+  links: Vec<Link>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Link {
+  name: String,
+  hash: String,
+  #[serde(default)]
+  size: u64,
+  #[serde(rename = "Type")]
+  link_type: Option<i32>,
+  target: Option<String>,
+}
+
+impl From<Ls> for LsResponse {
+  fn from(ls: Ls) -> Self {
+    LsResponse {
+      objects: ls
+        .objects
+        .into_iter()
+        .map(|object| LsObject {
+          hash: object.hash,
+          links: object
+            .links
+            .into_iter()
+            .map(|link| LsLink {
+              name: link.name,
+              hash: link.hash,
+              size: link.size,
+              // UnixFS data types: 0 = raw, 1 = directory, 2 = file, 4 = symlink, 5 = sharded directory
+              entry_type: match link.link_type {
+                Some(1) | Some(5) => IPFSEntryType::Directory,
+                Some(0) | Some(2) => IPFSEntryType::File,
+                Some(4) => IPFSEntryType::Symlink,
+                _ => IPFSEntryType::Other,
+              },
+              target: non_empty(link.target),
+            })
+            .collect(),
+        })
+        .collect(),
+    }
+  }
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pin-add (same for `pin/rm` and `pin/update`)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct Pins {
+  #[serde(default, deserialize_with = "null_as_empty")]
+  pins: Vec<String>,
+}
+
+impl From<Pins> for PinResponse {
+  fn from(pins: Pins) -> Self {
+    PinResponse { pins: pins.pins }
+  }
+}
+
+// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pin-ls
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct PinLs {
+  keys: Option<BTreeMap<String, PinLsKey>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PinLsKey {
+  #[serde(rename = "Type")]
+  pin_type: String,
+  name: Option<String>,
+}
+
+impl From<PinLs> for PinLsResponse {
+  fn from(ls: PinLs) -> Self {
+    PinLsResponse {
+      pins: ls
+        .keys
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(cid, key)| Pin {
+          cid,
+          // indirect pins of a listed path are reported as `indirect through <cid>`
+          pin_type: match key.pin_type.split(' ').next() {
+            Some("direct") => PinType::Direct,
+            Some("indirect") => PinType::Indirect,
+            Some("recursive") => PinType::Recursive,
+            _ => PinType::Other,
+          },
+          name: non_empty(key.name),
+        })
+        .collect(),
+    }
+  }
+}
+
+#[cfg(feature = "use-wasm-bindgen")]
+pub(super) fn parse<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, String> {
+  serde_json::from_slice(body).map_err(|e| format!("invalid response: {}", e))
+}
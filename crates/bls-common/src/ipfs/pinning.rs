@@ -0,0 +1,217 @@
+//! Remote pins through the IPFS Pinning Services API; JSON documents are converted into the response
+//! types handed to the guest. See https://ipfs.github.io/pinning-services-api-spec/
+use serde::{Deserialize, Serialize};
+use super::client::IPFSClient;
+use super::{PinRemoteAddOpts, PinRemoteLsOpts, PinRemoteLsResponse, PinRemoteRmOpts, RemotePin, RemotePinStatus};
+
+#[derive(Debug, Serialize)]
+struct PinBody<'a> {
+  cid: &'a str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  name: Option<&'a str>,
+  #[serde(skip_serializing_if = "<[_]>::is_empty")]
+  origins: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct PinStatus {
+  requestid: String,
+  status: RemotePinStatus,
+  created: String,
+  pin: PinObject,
+  #[serde(default)]
+  delegates: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PinObject {
+  cid: String,
+  name: Option<String>,
+}
+
+impl From<PinStatus> for RemotePin {
+  fn from(status: PinStatus) -> Self {
+    RemotePin {
+      request_id: status.requestid,
+      status: status.status,
+      created: status.created,
+      cid: status.pin.cid,
+      name: status.pin.name.filter(|name| !name.is_empty()),
+      delegates: status.delegates,
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct PinResults {
+  count: u64,
+  #[serde(default)]
+  results: Vec<PinStatus>,
+}
+
+impl From<PinResults> for PinRemoteLsResponse {
+  fn from(results: PinResults) -> Self {
+    PinRemoteLsResponse {
+      count: results.count,
+      results: results.results.into_iter().map(RemotePin::from).collect(),
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct Failure {
+  error: FailureError,
+}
+
+#[derive(Debug, Deserialize)]
+struct FailureError {
+  reason: String,
+  details: Option<String>,
+}
+
+pub(super) async fn add(client: &IPFSClient, opts: &PinRemoteAddOpts) -> Result<RemotePin, String> {
+  let body = PinBody { cid: &opts.cid, name: opts.name.as_deref(), origins: &opts.origins };
+  let body = serde_json::to_vec(&body).map_err(|e| e.to_string())?;
+  let response = client
+    .pinning_service_call("pin/remote/add", reqwest::Method::POST, "pins", &[], Some(body))
+    .await?;
+  Ok(parse::<PinStatus>(response)?.into())
+}
+
+pub(super) async fn ls(client: &IPFSClient, opts: &PinRemoteLsOpts) -> Result<PinRemoteLsResponse, String> {
+  let response = client
+    .pinning_service_call("pin/remote/ls", reqwest::Method::GET, "pins", &ls_query(opts), None)
+    .await?;
+  Ok(parse::<PinResults>(response)?.into())
+}
+
+pub(super) async fn rm(client: &IPFSClient, opts: &PinRemoteRmOpts) -> Result<(), String> {
+  // the id becomes a path segment
+  let valid = |c: char| c.is_ascii_alphanumeric() || "-._~".contains(c);
+  if opts.request_id.is_empty() || !opts.request_id.chars().all(valid) {
+    return Err(format!("invalid pin request id \"{}\"", opts.request_id));
+  }
+  let path = format!("pins/{}", opts.request_id);
+  let (status, body) = client
+    .pinning_service_call("pin/remote/rm", reqwest::Method::DELETE, &path, &[], None)
+    .await?;
+  check(status, &body)
+}
+
+fn ls_query(opts: &PinRemoteLsOpts) -> Vec<(&'static str, String)> {
+  let mut query = vec![];
+  if !opts.cid.is_empty() {
+    query.push(("cid", opts.cid.join(",")));
+  }
+  if let Some(name) = &opts.name {
+    query.push(("name", name.clone()));
+  }
+  if !opts.status.is_empty() {
+    let status: Vec<_> = opts.status.iter().map(|status| status_name(*status)).collect();
+    query.push(("status", status.join(",")));
+  }
+  if let Some(limit) = opts.limit {
+    query.push(("limit", limit.to_string()));
+  }
+  query
+}
+
+fn status_name(status: RemotePinStatus) -> &'static str {
+  match status {
+    RemotePinStatus::Queued => "queued",
+    RemotePinStatus::Pinning => "pinning",
+    RemotePinStatus::Pinned => "pinned",
+    RemotePinStatus::Failed => "failed",
+  }
+}
+
+fn parse<T: serde::de::DeserializeOwned>((status, body): (u16, Vec<u8>)) -> Result<T, String> {
+  check(status, &body)?;
+  serde_json::from_slice(&body).map_err(|e| format!("invalid pinning service response: {}", e))
+}
+
+/// Fails on any non-2xx status, with the reason given by the service (if any).
+fn check(status: u16, body: &[u8]) -> Result<(), String> {
+  if (200..300).contains(&status) {
+    return Ok(());
+  }
+  match serde_json::from_slice::<Failure>(body) {
+    Ok(Failure { error: FailureError { reason, details: Some(details) } }) => {
+      Err(format!("Error pinning service response: {} ({}: {})", status, reason, details))
+    }
+    Ok(Failure { error: FailureError { reason, details: None } }) => {
+      Err(format!("Error pinning service response: {} ({})", status, reason))
+    }
+    Err(_) => Err(format!("Error pinning service response: {}", status)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ipfs::{IPFSConfig, PinningServiceConfig};
+
+  #[test]
+  fn test_parse_pin_status() {
+    let results: PinResults = parse((200, br#"{"count":2,"results":[{
+      "requestid":"UniqueIdOfPinRequest","status":"pinned","created":"2020-07-27T17:32:28Z",
+      "pin":{"cid":"bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi","name":""},
+      "delegates":["/dnsaddr/pin-service.example.com"],"info":{"status_details":"done"}
+    }]}"#.to_vec())).unwrap();
+    let response = PinRemoteLsResponse::from(results);
+    assert_eq!(response.count, 2);
+    assert_eq!(response.results, vec![RemotePin {
+      request_id: "UniqueIdOfPinRequest".into(),
+      status: RemotePinStatus::Pinned,
+      created: "2020-07-27T17:32:28Z".into(),
+      cid: "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi".into(),
+      name: None,
+      delegates: vec!["/dnsaddr/pin-service.example.com".into()],
+    }]);
+
+    let err = parse::<PinResults>((401, br#"{"error":{"reason":"UNAUTHORIZED","details":"bad token"}}"#.to_vec()));
+    assert_eq!(err.unwrap_err(), "Error pinning service response: 401 (UNAUTHORIZED: bad token)");
+    assert_eq!(check(502, b"Bad Gateway").unwrap_err(), "Error pinning service response: 502");
+    assert!(check(202, b"").is_ok());
+  }
+
+  #[test]
+  fn test_request_documents() {
+    let origins = vec!["/ip4/203.0.113.1/tcp/4001/p2p/QmSourcePeerId".to_string()];
+    let body = PinBody { cid: "bafkrei", name: None, origins: &origins };
+    assert_eq!(
+      serde_json::to_string(&body).unwrap(),
+      r#"{"cid":"bafkrei","origins":["/ip4/203.0.113.1/tcp/4001/p2p/QmSourcePeerId"]}"#
+    );
+    let body = PinBody { cid: "bafkrei", name: Some("backup"), origins: &[] };
+    assert_eq!(serde_json::to_string(&body).unwrap(), r#"{"cid":"bafkrei","name":"backup"}"#);
+
+    assert!(ls_query(&PinRemoteLsOpts::default()).is_empty());
+    let opts = PinRemoteLsOpts {
+      cid: vec!["bafkrei".into(), "bafybei".into()],
+      status: vec![RemotePinStatus::Queued, RemotePinStatus::Pinning],
+      limit: Some(100),
+      ..Default::default()
+    };
+    assert_eq!(ls_query(&opts), vec![
+      ("cid", "bafkrei,bafybei".to_string()),
+      ("status", "queued,pinning".to_string()),
+      ("limit", "100".to_string()),
+    ]);
+  }
+
+  #[tokio::test]
+  async fn test_pinning_service_config() {
+    let opts = PinRemoteRmOpts { request_id: "UniqueIdOfPinRequest".into() };
+    let err = rm(&IPFSClient::default(), &opts).await.unwrap_err();
+    assert_eq!(err, "no remote pinning service configured");
+
+    let service = PinningServiceConfig { endpoint: "https://pin-service.example.com/psa".into(), access_token: "secret".into() };
+    assert!(!format!("{:?}", service).contains("secret"));
+    let client = IPFSClient::new(IPFSConfig { pinning_service: Some(service), ..Default::default() }).unwrap();
+    for request_id in ["", "../pins", "id?limit=1"] {
+      let opts = PinRemoteRmOpts { request_id: request_id.into() };
+      assert!(rm(&client, &opts).await.unwrap_err().starts_with("invalid pin request id"));
+    }
+  }
+}
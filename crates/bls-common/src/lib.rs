@@ -3,5 +3,10 @@
 pub mod http;
 pub mod s3;
 pub mod ipfs;
+pub mod permissions;
+pub mod quota;
+pub mod retry;
+#[cfg(feature = "use-wasm-bindgen")]
+pub mod time;
 
 mod macros;
@@ -0,0 +1,654 @@
+use std::str::FromStr;
+use crate::http::Method;
+
+/// Host part of an http(s) permission.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostPattern {
+  /// `*` - any host
+  Any,
+  /// `api.example.com` - exactly this host
+  Exact(String),
+  /// `*.example.com` - any subdomain of `example.com` (but not `example.com` itself)
+  Subdomain(String),
+}
+
+impl HostPattern {
+  fn matches(&self, host: &str) -> bool {
+    match self {
+      HostPattern::Any => true,
+      HostPattern::Exact(expected) => host == expected,
+      HostPattern::Subdomain(parent) => host
+        .strip_suffix(parent.as_str())
+        .and_then(|label| label.strip_suffix('.'))
+        .map(|label| !label.is_empty())
+        .unwrap_or(false),
+    }
+  }
+}
+
+/// A single outbound http(s) rule.
+///
+/// Grammar: `[!][METHODS ]scheme://host[:port][/path]`
+/// - `!` turns the rule into a deny rule; deny rules always win over allow rules.
+/// - `METHODS` is a comma separated list of methods (e.g. `GET,HEAD`) or `*`; all methods when omitted.
+/// - `host` is an exact host, `*.example.com` for any subdomain or `*` for any host;
+///   IPv6 addresses are written in brackets (e.g. `[::1]`).
+/// - `port` defaults to the scheme's default port.
+/// - `path` is a glob; `*` matches within a segment and `**` matches any number of segments.
+///   A path without wildcards matches itself and everything below it. Omitted path matches any path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpPermission {
+  pub deny: bool,
+  pub methods: Option<Vec<Method>>,
+  pub scheme: String,
+  pub host: HostPattern,
+  pub port: u16,
+  pub path: String,
+}
+
+impl HttpPermission {
+  pub fn matches(&self, method: &Method, url: &reqwest::Url) -> bool {
+    if url.scheme() != self.scheme {
+      return false;
+    }
+    let host = match url.host_str() {
+      Some(host) => host.to_ascii_lowercase(),
+      None => return false,
+    };
+    if !self.host.matches(&host) {
+      return false;
+    }
+    if url.port_or_known_default() != Some(self.port) {
+      return false;
+    }
+    if let Some(methods) = &self.methods {
+      if !methods.contains(method) {
+        return false;
+      }
+    }
+    path_matches(&self.path, &normalize_path(url.path()))
+  }
+}
+
+impl FromStr for HttpPermission {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = |reason: &str| format!("invalid permission \"{}\": {}", s, reason);
+
+    let rest = s.trim();
+    let (deny, rest) = match rest.strip_prefix('!') {
+      Some(rest) => (true, rest.trim_start()),
+      None => (false, rest),
+    };
+    let (methods, target) = match rest.split_once(char::is_whitespace) {
+      Some((methods, target)) => (parse_methods(methods).map_err(|e| invalid(&e))?, target.trim()),
+      None => (None, rest),
+    };
+
+    let (scheme, rest) = target.split_once("://").ok_or_else(|| invalid("missing scheme"))?;
+    let scheme = scheme.to_ascii_lowercase();
+    let default_port = match scheme.as_str() {
+      "http" => 80,
+      "https" => 443,
+      _ => return Err(invalid("only http and https schemes are supported")),
+    };
+    if rest.contains(|c| c == '?' || c == '#') {
+      return Err(invalid("query strings and fragments are not supported"));
+    }
+
+    let (authority, path) = match rest.find('/') {
+      Some(idx) => (&rest[..idx], &rest[idx..]),
+      None => (rest, "/"),
+    };
+    if authority.contains('@') {
+      return Err(invalid("credentials are not allowed in the host"));
+    }
+    let (host, port) = match authority.strip_prefix('[') {
+      Some(literal) => {
+        let (address, port) = literal.split_once(']').ok_or_else(|| invalid("invalid host"))?;
+        let port = match port {
+          "" => default_port,
+          port => port.strip_prefix(':').and_then(|port| port.parse::<u16>().ok()).ok_or_else(|| invalid("invalid port"))?,
+        };
+        (parse_ipv6_host(address).map_err(invalid)?, port)
+      }
+      None => {
+        let (host, port) = match authority.rsplit_once(':') {
+          Some((host, port)) => (host, port.parse::<u16>().map_err(|_| invalid("invalid port"))?),
+          None => (authority, default_port),
+        };
+        (parse_host(host).map_err(invalid)?, port)
+      }
+    };
+
+    Ok(HttpPermission {
+      deny,
+      methods,
+      scheme,
+      host,
+      port,
+      path: normalize_path(path),
+    })
+  }
+}
+
+fn parse_host(host: &str) -> Result<HostPattern, &'static str> {
+  let host = host.to_ascii_lowercase();
+  if host == "*" {
+    return Ok(HostPattern::Any);
+  }
+  let (name, subdomain) = match host.strip_prefix("*.") {
+    Some(parent) => (parent, true),
+    None => (host.as_str(), false),
+  };
+  if name.is_empty() {
+    return Err("missing host");
+  }
+  if name.contains('*') {
+    return Err("wildcards are only allowed as a leading `*.` label");
+  }
+  if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.') {
+    return Err("invalid host");
+  }
+  Ok(match subdomain {
+    true => HostPattern::Subdomain(name.to_string()),
+    false => HostPattern::Exact(name.to_string()),
+  })
+}
+
+/// The bracketed address in the form `Url::host_str` reports it (e.g. `[::1]`), so rules match
+/// however the address is written.
+fn parse_ipv6_host(address: &str) -> Result<HostPattern, &'static str> {
+  let address = address.parse::<std::net::Ipv6Addr>().map_err(|_| "invalid IPv6 host")?;
+  let url = reqwest::Url::parse(&format!("http://[{}]/", address)).map_err(|_| "invalid IPv6 host")?;
+  let host = url.host_str().ok_or("invalid IPv6 host")?;
+  Ok(HostPattern::Exact(host.to_string()))
+}
+
+fn parse_methods(s: &str) -> Result<Option<Vec<Method>>, String> {
+  if s == "*" {
+    return Ok(None);
+  }
+  s.split(',')
+    .map(|m| Method::from_str(m.trim()).map_err(|_| format!("unknown method \"{}\"", m)))
+    .collect::<Result<Vec<_>, _>>()
+    .map(Some)
+}
+
+/// Normalizes a path the way servers resolve it, so encoded variants cannot dodge a rule:
+/// percent-escapes of unreserved characters are decoded (`%61dmin` is `admin`) and `.`/`..` segments removed.
+fn normalize_path(path: &str) -> String {
+  let bytes = path.as_bytes();
+  let mut decoded = String::with_capacity(path.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let escaped = match bytes.get(i + 1..i + 3) {
+      Some(hex) if bytes[i] == b'%' => std::str::from_utf8(hex).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+      _ => None,
+    };
+    match escaped {
+      Some(byte) if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) => {
+        decoded.push(byte as char);
+        i += 3;
+      }
+      Some(_) => {
+        decoded.push_str(&path[i..i + 3].to_ascii_uppercase());
+        i += 3;
+      }
+      None => {
+        // `path` is a str - copy the whole (possibly multi-byte) character
+        let c = path[i..].chars().next().unwrap_or_default();
+        decoded.push(c);
+        i += c.len_utf8();
+      }
+    }
+  }
+
+  let mut segments: Vec<&str> = vec![];
+  let mut parts = decoded.trim_start_matches('/').split('/').peekable();
+  while let Some(segment) = parts.next() {
+    match segment {
+      "." | ".." => {
+        if segment == ".." {
+          segments.pop();
+        }
+        // keep the directory form of a trailing dot-segment (`/a/b/..` is `/a/`)
+        if parts.peek().is_none() {
+          segments.push("");
+        }
+      }
+      _ => segments.push(segment),
+    }
+  }
+  format!("/{}", segments.join("/"))
+}
+
+/// Matches a request path against a permission path glob.
+fn path_matches(pattern: &str, path: &str) -> bool {
+  if pattern == "/" || pattern.is_empty() {
+    return true;
+  }
+  if !pattern.contains('*') {
+    let prefix = pattern.trim_end_matches('/');
+    return path == prefix
+      || path
+        .strip_prefix(prefix)
+        .map(|rest| rest.starts_with('/'))
+        .unwrap_or(false);
+  }
+  let pattern: Vec<&str> = pattern.trim_start_matches('/').split('/').collect();
+  let path: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+  segments_match(&pattern, &path)
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+  match pattern.split_first() {
+    None => path.is_empty(),
+    Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+    Some((segment, rest)) => match path.split_first() {
+      Some((first, path_rest)) => segment_matches(segment, first) && segments_match(rest, path_rest),
+      None => false,
+    },
+  }
+}
+
+/// Matches a single path segment where `*` matches any run of characters.
+fn segment_matches(pattern: &str, segment: &str) -> bool {
+  let mut parts = pattern.split('*');
+  let first = parts.next().unwrap_or_default();
+  let mut rest = match segment.strip_prefix(first) {
+    Some(rest) => rest,
+    None => return false,
+  };
+  let parts: Vec<&str> = parts.collect();
+  for (i, part) in parts.iter().enumerate() {
+    if i == parts.len() - 1 {
+      return rest.ends_with(part);
+    }
+    match rest.find(part) {
+      Some(idx) => rest = &rest[idx + part.len()..],
+      None => return false,
+    }
+  }
+  rest.is_empty()
+}
+
+/// A rule restricting the buckets an S3 credential profile may be used for.
+///
+/// Grammar: `[!]s3://profile/bucket`
+/// - `!` turns the rule into a deny rule; deny rules always win over allow rules.
+/// - `profile` and `bucket` are names where `*` matches any run of characters.
+/// - calls that are not scoped to a bucket (e.g. listing buckets) require a `*` bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Permission {
+  pub deny: bool,
+  pub profile: String,
+  pub bucket: String,
+}
+
+impl S3Permission {
+  pub fn matches(&self, profile: &str, bucket: Option<&str>) -> bool {
+    if !segment_matches(&self.profile, profile) {
+      return false;
+    }
+    match bucket {
+      Some(bucket) => segment_matches(&self.bucket, bucket),
+      None => self.bucket == "*",
+    }
+  }
+}
+
+impl FromStr for S3Permission {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = |reason: &str| format!("invalid permission \"{}\": {}", s, reason);
+
+    let rest = s.trim();
+    let (deny, rest) = match rest.strip_prefix('!') {
+      Some(rest) => (true, rest.trim_start()),
+      None => (false, rest),
+    };
+    let rest = rest
+      .get(..5)
+      .filter(|scheme| scheme.eq_ignore_ascii_case("s3://"))
+      .map(|_| &rest[5..])
+      .ok_or_else(|| invalid("missing s3 scheme"))?;
+    let (profile, bucket) = rest.split_once('/').ok_or_else(|| invalid("missing bucket"))?;
+    let bucket = bucket.strip_suffix('/').unwrap_or(bucket);
+    if profile.is_empty() {
+      return Err(invalid("missing profile"));
+    }
+    if bucket.is_empty() {
+      return Err(invalid("missing bucket"));
+    }
+    if bucket.contains('/') {
+      return Err(invalid("object paths are not supported"));
+    }
+
+    Ok(S3Permission { deny, profile: profile.to_string(), bucket: bucket.to_string() })
+  }
+}
+
+/// A rule restricting the IPFS RPC commands a guest may call.
+///
+/// Grammar: `[!]ipfs://command`
+/// - `!` turns the rule into a deny rule; deny rules always win over allow rules.
+/// - `command` is an RPC path (e.g. `files/read`) matched like an http path: `files` matches every `files/*`
+///   command, `*` matches within a segment and `**` any number of segments. A sole `*` matches every command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpfsPermission {
+  pub deny: bool,
+  pub command: String,
+}
+
+impl IpfsPermission {
+  pub fn matches(&self, command: &str) -> bool {
+    self.command == "*" || path_matches(&format!("/{}", self.command), &format!("/{}", command))
+  }
+}
+
+impl FromStr for IpfsPermission {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = |reason: &str| format!("invalid permission \"{}\": {}", s, reason);
+
+    let rest = s.trim();
+    let (deny, rest) = match rest.strip_prefix('!') {
+      Some(rest) => (true, rest.trim_start()),
+      None => (false, rest),
+    };
+    let command = rest
+      .get(..7)
+      .filter(|scheme| scheme.eq_ignore_ascii_case("ipfs://"))
+      .map(|_| rest[7..].trim_matches('/'))
+      .ok_or_else(|| invalid("missing ipfs scheme"))?;
+    if command.is_empty() {
+      return Err(invalid("missing command"));
+    }
+    if !command.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '/' || c == '*') {
+      return Err(invalid("invalid command"));
+    }
+
+    Ok(IpfsPermission { deny, command: command.to_ascii_lowercase() })
+  }
+}
+
+/// A parsed permission from `BlocklessConfig.permissions`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Permission {
+  Http(HttpPermission),
+  S3(S3Permission),
+  Ipfs(IpfsPermission),
+}
+
+impl FromStr for Permission {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let target = s.trim().trim_start_matches('!').trim_start();
+    if target.get(..5).is_some_and(|scheme| scheme.eq_ignore_ascii_case("s3://")) {
+      return Ok(Permission::S3(s.parse()?));
+    }
+    if target.get(..7).is_some_and(|scheme| scheme.eq_ignore_ascii_case("ipfs://")) {
+      return Ok(Permission::Ipfs(s.parse()?));
+    }
+    Ok(Permission::Http(s.parse()?))
+  }
+}
+
+/// The full set of permissions granted to a guest; validated once when the runtime is created.
+#[derive(Debug, Clone, Default)]
+pub struct Permissions {
+  rules: Vec<Permission>,
+}
+
+impl Permissions {
+  pub fn parse(permissions: &[String]) -> Result<Self, String> {
+    let rules = permissions
+      .iter()
+      .map(|p| p.parse::<Permission>())
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(Permissions { rules })
+  }
+
+  pub fn http_rules(&self) -> impl Iterator<Item = &HttpPermission> {
+    self.rules.iter().filter_map(|rule| match rule {
+      Permission::Http(rule) => Some(rule),
+      _ => None,
+    })
+  }
+
+  pub fn s3_rules(&self) -> impl Iterator<Item = &S3Permission> {
+    self.rules.iter().filter_map(|rule| match rule {
+      Permission::S3(rule) => Some(rule),
+      _ => None,
+    })
+  }
+
+  pub fn ipfs_rules(&self) -> impl Iterator<Item = &IpfsPermission> {
+    self.rules.iter().filter_map(|rule| match rule {
+      Permission::Ipfs(rule) => Some(rule),
+      _ => None,
+    })
+  }
+
+  /// Returns true if at least one allow rule matches and no deny rule matches.
+  pub fn allows_http(&self, method: &Method, url: &str) -> bool {
+    let url = match reqwest::Url::parse(url) {
+      Ok(url) => url,
+      Err(_) => return false,
+    };
+    let mut allowed = false;
+    for rule in self.http_rules().filter(|rule| rule.matches(method, &url)) {
+      if rule.deny {
+        return false;
+      }
+      allowed = true;
+    }
+    allowed
+  }
+
+  /// Returns true if the credential profile may be used for the bucket (`None` for calls not scoped to a bucket).
+  pub fn allows_s3(&self, profile: &str, bucket: Option<&str>) -> bool {
+    let mut allowed = false;
+    for rule in self.s3_rules().filter(|rule| rule.matches(profile, bucket)) {
+      if rule.deny {
+        return false;
+      }
+      allowed = true;
+    }
+    allowed
+  }
+
+  /// Returns true if the guest may call the IPFS RPC command (e.g. `files/read`).
+  pub fn allows_ipfs(&self, command: &str) -> bool {
+    let mut allowed = false;
+    for rule in self.ipfs_rules().filter(|rule| rule.matches(command)) {
+      if rule.deny {
+        return false;
+      }
+      allowed = true;
+    }
+    allowed
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn permissions(rules: &[&str]) -> Permissions {
+    Permissions::parse(&rules.iter().map(|r| r.to_string()).collect::<Vec<_>>()).unwrap()
+  }
+
+  #[test]
+  fn test_parse_http_permission() {
+    let perm: HttpPermission = "!GET,POST https://*.Example.com:8443/api/**".parse().unwrap();
+    assert!(perm.deny);
+    assert_eq!(perm.methods, Some(vec![Method::Get, Method::Post]));
+    assert_eq!(perm.scheme, "https");
+    assert_eq!(perm.host, HostPattern::Subdomain("example.com".into()));
+    assert_eq!(perm.port, 8443);
+    assert_eq!(perm.path, "/api/**");
+
+    let perm: HttpPermission = "http://example.com".parse().unwrap();
+    assert!(!perm.deny);
+    assert_eq!(perm.methods, None);
+    assert_eq!(perm.host, HostPattern::Exact("example.com".into()));
+    assert_eq!(perm.port, 80);
+    assert_eq!(perm.path, "/");
+  }
+
+  #[test]
+  fn test_parse_malformed_permissions() {
+    for malformed in [
+      "example.com",
+      "ftp://example.com",
+      "https://",
+      "https://example.com:99999",
+      "https://api.*.example.com",
+      "https://user@example.com",
+      "https://[::1",
+      "https://[::g]",
+      "https://[::1]8080",
+      "https://example.com/?a=b",
+      "GET,(POST) https://example.com",
+    ] {
+      let err = malformed.parse::<HttpPermission>().unwrap_err();
+      assert!(err.contains(malformed), "{}", err);
+    }
+  }
+
+  #[test]
+  fn test_host_matching() {
+    let perms = permissions(&["https://api.example.com", "https://*.cdn.example.com"]);
+    assert!(perms.allows_http(&Method::Get, "https://api.example.com/todos/1"));
+    assert!(perms.allows_http(&Method::Get, "https://API.example.com"));
+    assert!(!perms.allows_http(&Method::Get, "https://api.example.com.evil.net/"));
+    assert!(!perms.allows_http(&Method::Get, "https://api.example.com@evil.net/"));
+    assert!(!perms.allows_http(&Method::Get, "http://api.example.com/"));
+    assert!(!perms.allows_http(&Method::Get, "https://api.example.com:8443/"));
+    assert!(perms.allows_http(&Method::Get, "https://a.cdn.example.com/x"));
+    assert!(perms.allows_http(&Method::Get, "https://a.b.cdn.example.com/x"));
+    assert!(!perms.allows_http(&Method::Get, "https://cdn.example.com/x"));
+    assert!(!perms.allows_http(&Method::Get, "https://evilcdn.example.com/x"));
+
+    let perms = permissions(&["http://[::1]:8080", "https://[2001:DB8:0:0:0:0:0:1]/api"]);
+    assert!(perms.allows_http(&Method::Get, "http://[::1]:8080/todos"));
+    assert!(perms.allows_http(&Method::Get, "http://[0:0:0:0:0:0:0:1]:8080/"));
+    assert!(!perms.allows_http(&Method::Get, "http://[::1]/"));
+    assert!(!perms.allows_http(&Method::Get, "http://[::2]:8080/"));
+    assert!(perms.allows_http(&Method::Get, "https://[2001:db8::1]/api/users"));
+    assert!(!perms.allows_http(&Method::Get, "https://[2001:db8::1]/admin"));
+  }
+
+  #[test]
+  fn test_path_matching() {
+    let perms = permissions(&["https://example.com/todos/1", "https://example.com/users/*/posts/**"]);
+    assert!(perms.allows_http(&Method::Get, "https://example.com/todos/1"));
+    assert!(perms.allows_http(&Method::Get, "https://example.com/todos/1/comments?page=2"));
+    assert!(!perms.allows_http(&Method::Get, "https://example.com/todos/10"));
+    assert!(!perms.allows_http(&Method::Get, "https://example.com/todos/2/../1x"));
+    assert!(perms.allows_http(&Method::Get, "https://example.com/users/42/posts"));
+    assert!(perms.allows_http(&Method::Get, "https://example.com/users/42/posts/7/likes"));
+    assert!(!perms.allows_http(&Method::Get, "https://example.com/users/42/likes"));
+
+    assert!(segment_matches("*.json", "data.json"));
+    assert!(segment_matches("v*-beta", "v2-beta"));
+    assert!(!segment_matches("*.json", "data.xml"));
+  }
+
+  #[test]
+  fn test_methods_and_deny_rules() {
+    let perms = permissions(&[
+      "GET,HEAD https://example.com",
+      "POST https://example.com/api",
+      "!https://example.com/api/admin/**",
+    ]);
+    assert!(perms.allows_http(&Method::Get, "https://example.com/"));
+    assert!(!perms.allows_http(&Method::Delete, "https://example.com/"));
+    assert!(perms.allows_http(&Method::Post, "https://example.com/api/items"));
+    assert!(!perms.allows_http(&Method::Post, "https://example.com/other"));
+    assert!(!perms.allows_http(&Method::Post, "https://example.com/api/admin/users"));
+    assert!(!perms.allows_http(&Method::Get, "https://example.com/api/x/../admin/users"));
+    // encoded variants resolve to the denied path
+    assert!(!perms.allows_http(&Method::Post, "https://example.com/api/%61dmin/users"));
+    assert!(!perms.allows_http(&Method::Post, "https://example.com/api/%2e/admin/users"));
+    assert!(!perms.allows_http(&Method::Post, "https://example.com/api/x/%2E%2E/adm%69n"));
+  }
+
+  #[test]
+  fn test_normalize_path() {
+    assert_eq!(normalize_path("/"), "/");
+    assert_eq!(normalize_path("/api/%61dmin/%7Euser"), "/api/admin/~user");
+    assert_eq!(normalize_path("/a%2fb/%c3%a9"), "/a%2Fb/%C3%A9");
+    assert_eq!(normalize_path("/a/./b/../c"), "/a/c");
+    assert_eq!(normalize_path("/a/b/.."), "/a/");
+    assert_eq!(normalize_path("/../../a"), "/a");
+    assert_eq!(normalize_path("/a/%zz/%4"), "/a/%zz/%4");
+    assert_eq!(normalize_path("/caf\u{e9}/"), "/caf\u{e9}/");
+  }
+
+  #[test]
+  fn test_extension_methods() {
+    let perms = permissions(&["PATCH,PURGE https://example.com", "!OPTIONS https://example.com/private"]);
+    assert!(perms.allows_http(&Method::Patch, "https://example.com/items/1"));
+    assert!(perms.allows_http(&Method::Other("PURGE".into()), "https://example.com/cache"));
+    assert!(!perms.allows_http(&Method::Other("LINK".into()), "https://example.com/"));
+    assert!(!perms.allows_http(&Method::Options, "https://example.com/private"));
+
+    let perms = permissions(&["https://example.com", "!GET https://example.com/admin"]);
+    assert!(!perms.allows_http(&Method::Other("get".into()), "https://example.com/admin"));
+  }
+
+  #[test]
+  fn test_s3_permissions() {
+    let perm: S3Permission = "!S3://media/private-*".parse().unwrap();
+    assert_eq!(perm, S3Permission { deny: true, profile: "media".into(), bucket: "private-*".into() });
+    for malformed in ["s3://media", "s3:///bucket", "s3://media/", "s3://media/bucket/key"] {
+      let err = malformed.parse::<S3Permission>().unwrap_err();
+      assert!(err.contains(malformed), "{}", err);
+    }
+
+    let perms = permissions(&["s3://media/*", "!s3://media/private-*", "s3://backup/backup-2023", "https://example.com"]);
+    assert_eq!(perms.http_rules().count(), 1);
+    assert!(perms.allows_s3("media", Some("photos")));
+    assert!(perms.allows_s3("media", None));
+    assert!(!perms.allows_s3("media", Some("private-keys")));
+    assert!(perms.allows_s3("backup", Some("backup-2023")));
+    assert!(!perms.allows_s3("backup", Some("backup-2024")));
+    assert!(!perms.allows_s3("backup", None));
+    assert!(!perms.allows_s3("other", Some("photos")));
+    assert!(!perms.allows_http(&Method::Get, "s3://media/photos"));
+  }
+
+  #[test]
+  fn test_no_permissions_denies_all() {
+    let perms = Permissions::default();
+    assert!(!perms.allows_http(&Method::Get, "https://example.com/"));
+    assert!(!permissions(&["https://*"]).allows_http(&Method::Get, "not a url"));
+    assert!(!perms.allows_s3("media", Some("photos")));
+    assert!(!perms.allows_ipfs("version"));
+  }
+
+  #[test]
+  fn test_ipfs_permissions() {
+    let perm: IpfsPermission = "! IPFS://Files/Rm".parse().unwrap();
+    assert_eq!(perm, IpfsPermission { deny: true, command: "files/rm".into() });
+    for malformed in ["ipfs://", "ipfs:///", "ipfs://files?arg=/"] {
+      let err = malformed.parse::<IpfsPermission>().unwrap_err();
+      assert!(err.contains(malformed), "{}", err);
+    }
+
+    let perms = permissions(&["ipfs://files", "!ipfs://files/rm", "ipfs://version"]);
+    assert_eq!(perms.http_rules().count(), 0);
+    assert!(perms.allows_ipfs("files/read"));
+    assert!(perms.allows_ipfs("version"));
+    assert!(!perms.allows_ipfs("files/rm"));
+    assert!(!perms.allows_ipfs("add"));
+
+    let perms = permissions(&["ipfs://*", "!ipfs://files/*"]);
+    assert!(perms.allows_ipfs("add"));
+    assert!(perms.allows_ipfs("files"));
+    assert!(!perms.allows_ipfs("files/write"));
+  }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+
+/// Status returned by a host import (instead of `0`) when the call was rejected by a quota;
+/// the callback is not invoked in that case.
+pub const QUOTA_EXCEEDED_STATUS: u32 = 2;
+
+/// Host extensions that are subject to quotas.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Extension {
+  Http,
+  S3,
+  Ipfs,
+}
+
+/// Limits of a single extension; unset limits are not enforced.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Quota {
+  /// total number of calls
+  pub max_calls: Option<u64>,
+  /// number of calls in flight at the same time
+  pub max_concurrent: Option<u32>,
+  /// total bytes delivered to the guest (serialized results)
+  pub max_bytes_in: Option<u64>,
+  /// total bytes sent by the guest (serialized requests)
+  pub max_bytes_out: Option<u64>,
+  pub rate: Option<RateLimit>,
+}
+
+/// Token bucket: holds up to `burst` calls and refills at `per_second` calls per second.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimit {
+  pub per_second: f64,
+  pub burst: u32,
+}
+
+/// Per-instance quotas (`BlocklessConfig.quotas`).
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct QuotaConfig {
+  pub http: Quota,
+  pub s3: Quota,
+  pub ipfs: Quota,
+}
+
+impl QuotaConfig {
+  pub fn quota(&self, extension: Extension) -> &Quota {
+    match extension {
+      Extension::Http => &self.http,
+      Extension::S3 => &self.s3,
+      Extension::Ipfs => &self.ipfs,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum QuotaError {
+  MaxCalls(u64),
+  MaxConcurrent(u32),
+  MaxBytesIn(u64),
+  MaxBytesOut(u64),
+  RateLimited,
+}
+
+impl std::fmt::Display for QuotaError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      QuotaError::MaxCalls(max) => write!(f, "quota exceeded: more than {} calls", max),
+      QuotaError::MaxConcurrent(max) => write!(f, "quota exceeded: more than {} concurrent calls", max),
+      QuotaError::MaxBytesIn(max) => write!(f, "quota exceeded: more than {} bytes received", max),
+      QuotaError::MaxBytesOut(max) => write!(f, "quota exceeded: more than {} bytes sent", max),
+      QuotaError::RateLimited => write!(f, "quota exceeded: rate limited"),
+    }
+  }
+}
+
+/// Usage of a single extension, as reported by `Quotas::usage`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaUsage {
+  pub calls: u64,
+  pub in_flight: u32,
+  pub bytes_in: u64,
+  pub bytes_out: u64,
+  pub rejected: u64,
+}
+
+#[derive(Debug, Default)]
+struct UsageState {
+  usage: QuotaUsage,
+  tokens: f64,
+  last_refill: Option<f64>,
+}
+
+/// Quota state of a runtime instance; clones share the usage.
+#[derive(Debug, Clone, Default)]
+pub struct Quotas {
+  config: QuotaConfig,
+  state: Arc<Mutex<HashMap<Extension, UsageState>>>,
+}
+
+impl Quotas {
+  pub fn new(config: QuotaConfig) -> Self {
+    Quotas { config, state: Default::default() }
+  }
+
+  pub fn config(&self) -> &QuotaConfig {
+    &self.config
+  }
+
+  /// Admits a new call sending `bytes_out` bytes at `now` (in seconds);
+  /// the call counts as in flight until the permit is dropped.
+  pub fn acquire(&self, extension: Extension, bytes_out: u64, now: f64) -> Result<QuotaPermit, QuotaError> {
+    let quota = self.config.quota(extension);
+    let mut state = self.state.lock().unwrap();
+    let state = state.entry(extension).or_default();
+    let result = Self::admit(quota, state, bytes_out, now);
+    match result {
+      Ok(()) => {
+        state.usage.calls += 1;
+        state.usage.in_flight += 1;
+        state.usage.bytes_out += bytes_out;
+        Ok(QuotaPermit { quotas: self.clone(), extension })
+      }
+      Err(e) => {
+        state.usage.rejected += 1;
+        Err(e)
+      }
+    }
+  }
+
+  /// Charges a retry of an admitted call like another call (`max_calls` and the rate limit);
+  /// a rejected retry is not made.
+  pub fn charge_retry(&self, extension: Extension, now: f64) -> Result<(), QuotaError> {
+    let quota = self.config.quota(extension);
+    let mut state = self.state.lock().unwrap();
+    let state = state.entry(extension).or_default();
+    let result = Self::check_calls(quota, state).and_then(|_| Self::take_token(quota, state, now));
+    match result {
+      Ok(()) => state.usage.calls += 1,
+      Err(_) => state.usage.rejected += 1,
+    }
+    result
+  }
+
+  fn admit(quota: &Quota, state: &mut UsageState, bytes_out: u64, now: f64) -> Result<(), QuotaError> {
+    Self::check_calls(quota, state)?;
+    let usage = &state.usage;
+    if let Some(max) = quota.max_concurrent.filter(|max| usage.in_flight >= *max) {
+      return Err(QuotaError::MaxConcurrent(max));
+    }
+    if let Some(max) = quota.max_bytes_out.filter(|max| usage.bytes_out + bytes_out > *max) {
+      return Err(QuotaError::MaxBytesOut(max));
+    }
+    if let Some(max) = quota.max_bytes_in.filter(|max| usage.bytes_in >= *max) {
+      return Err(QuotaError::MaxBytesIn(max));
+    }
+    Self::take_token(quota, state, now)
+  }
+
+  fn check_calls(quota: &Quota, state: &UsageState) -> Result<(), QuotaError> {
+    match quota.max_calls.filter(|max| state.usage.calls >= *max) {
+      Some(max) => Err(QuotaError::MaxCalls(max)),
+      None => Ok(()),
+    }
+  }
+
+  fn take_token(quota: &Quota, state: &mut UsageState, now: f64) -> Result<(), QuotaError> {
+    if let Some(rate) = &quota.rate {
+      // refill the bucket for the time elapsed since the last call
+      let burst = rate.burst as f64;
+      let elapsed = state.last_refill.map(|last| (now - last).max(0.0));
+      state.tokens = match elapsed {
+        Some(elapsed) => (state.tokens + elapsed * rate.per_second).min(burst),
+        None => burst,
+      };
+      state.last_refill = Some(now);
+      if state.tokens < 1.0 {
+        return Err(QuotaError::RateLimited);
+      }
+      state.tokens -= 1.0;
+    }
+    Ok(())
+  }
+
+  /// Checks that the extension may still deliver data to the guest (e.g. further body reads).
+  pub fn check_bytes_in(&self, extension: Extension) -> Result<(), QuotaError> {
+    let quota = self.config.quota(extension);
+    let mut state = self.state.lock().unwrap();
+    let state = state.entry(extension).or_default();
+    match quota.max_bytes_in.filter(|max| state.usage.bytes_in >= *max) {
+      Some(max) => {
+        state.usage.rejected += 1;
+        Err(QuotaError::MaxBytesIn(max))
+      }
+      None => Ok(()),
+    }
+  }
+
+  /// Accounts for data delivered to the guest.
+  pub fn record_bytes_in(&self, extension: Extension, bytes: u64) {
+    let mut state = self.state.lock().unwrap();
+    state.entry(extension).or_default().usage.bytes_in += bytes;
+  }
+
+  pub fn usage(&self, extension: Extension) -> QuotaUsage {
+    let state = self.state.lock().unwrap();
+    state.get(&extension).map(|state| state.usage.clone()).unwrap_or_default()
+  }
+}
+
+/// Current time in seconds, for charging retries made by the clients.
+/// An admitted call; releases its concurrency slot when dropped.
+#[derive(Debug)]
+pub struct QuotaPermit {
+  quotas: Quotas,
+  extension: Extension,
+}
+
+impl QuotaPermit {
+  pub fn record_bytes_in(&self, bytes: u64) {
+    self.quotas.record_bytes_in(self.extension, bytes);
+  }
+}
+
+impl Drop for QuotaPermit {
+  fn drop(&mut self) {
+    let mut state = self.quotas.state.lock().unwrap();
+    if let Some(state) = state.get_mut(&self.extension) {
+      state.usage.in_flight -= 1;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn quotas(http: Quota) -> Quotas {
+    Quotas::new(QuotaConfig { http, ..Default::default() })
+  }
+
+  #[test]
+  fn test_config_serde() {
+    let config: QuotaConfig = serde_json::from_str(
+      r#"{"http":{"maxCalls":10,"rate":{"perSecond":2,"burst":5}},"s3":{"maxBytesOut":1024}}"#
+    ).unwrap();
+    assert_eq!(config.http.max_calls, Some(10));
+    assert_eq!(config.http.rate, Some(RateLimit { per_second: 2.0, burst: 5 }));
+    assert_eq!(config.s3.max_bytes_out, Some(1024));
+    assert_eq!(config.ipfs, Quota::default());
+  }
+
+  #[test]
+  fn test_max_calls_and_concurrency() {
+    let quotas = quotas(Quota { max_calls: Some(3), max_concurrent: Some(2), ..Default::default() });
+    let first = quotas.acquire(Extension::Http, 0, 0.0).unwrap();
+    let _second = quotas.acquire(Extension::Http, 0, 0.0).unwrap();
+    assert_eq!(quotas.acquire(Extension::Http, 0, 0.0).unwrap_err(), QuotaError::MaxConcurrent(2));
+
+    drop(first);
+    let _third = quotas.acquire(Extension::Http, 0, 0.0).unwrap();
+    assert_eq!(quotas.acquire(Extension::Http, 0, 0.0).unwrap_err(), QuotaError::MaxCalls(3));
+
+    // other extensions are not limited
+    assert!(quotas.acquire(Extension::S3, 0, 0.0).is_ok());
+
+    let usage = quotas.usage(Extension::Http);
+    assert_eq!((usage.calls, usage.in_flight, usage.rejected), (3, 2, 2));
+  }
+
+  #[test]
+  fn test_charge_retry() {
+    let counted = quotas(Quota { max_calls: Some(3), max_concurrent: Some(1), ..Default::default() });
+    let _permit = counted.acquire(Extension::Http, 0, 0.0).unwrap();
+    // retries are not concurrent calls
+    assert!(counted.charge_retry(Extension::Http, 0.0).is_ok());
+    assert!(counted.charge_retry(Extension::Http, 0.0).is_ok());
+    assert_eq!(counted.charge_retry(Extension::Http, 0.0).unwrap_err(), QuotaError::MaxCalls(3));
+    let usage = counted.usage(Extension::Http);
+    assert_eq!((usage.calls, usage.in_flight, usage.rejected), (3, 1, 1));
+
+    let limited = quotas(Quota { rate: Some(RateLimit { per_second: 1.0, burst: 1 }), ..Default::default() });
+    let _permit = limited.acquire(Extension::Http, 0, 0.0).unwrap();
+    assert_eq!(limited.charge_retry(Extension::Http, 0.5).unwrap_err(), QuotaError::RateLimited);
+    assert!(limited.charge_retry(Extension::Http, 1.0).is_ok());
+  }
+
+  #[test]
+  fn test_bytes() {
+    let quotas = quotas(Quota { max_bytes_out: Some(100), max_bytes_in: Some(10), ..Default::default() });
+    let permit = quotas.acquire(Extension::Http, 60, 0.0).unwrap();
+    assert_eq!(quotas.acquire(Extension::Http, 60, 0.0).unwrap_err(), QuotaError::MaxBytesOut(100));
+
+    assert!(quotas.check_bytes_in(Extension::Http).is_ok());
+    permit.record_bytes_in(10);
+    assert_eq!(quotas.check_bytes_in(Extension::Http).unwrap_err(), QuotaError::MaxBytesIn(10));
+    assert_eq!(quotas.acquire(Extension::Http, 1, 0.0).unwrap_err(), QuotaError::MaxBytesIn(10));
+  }
+
+  #[test]
+  fn test_rate_limit() {
+    let quotas = quotas(Quota { rate: Some(RateLimit { per_second: 2.0, burst: 2 }), ..Default::default() });
+    assert!(quotas.acquire(Extension::Http, 0, 0.0).is_ok());
+    assert!(quotas.acquire(Extension::Http, 0, 0.0).is_ok());
+    assert_eq!(quotas.acquire(Extension::Http, 0, 0.1).unwrap_err(), QuotaError::RateLimited);
+
+    // half a second refills one token
+    assert!(quotas.acquire(Extension::Http, 0, 0.6).is_ok());
+    assert_eq!(quotas.acquire(Extension::Http, 0, 0.6).unwrap_err(), QuotaError::RateLimited);
+
+    // the bucket never holds more than `burst` tokens
+    assert!(quotas.acquire(Extension::Http, 0, 100.0).is_ok());
+    assert!(quotas.acquire(Extension::Http, 0, 100.0).is_ok());
+    assert!(quotas.acquire(Extension::Http, 0, 100.0).is_err());
+  }
+}
@@ -0,0 +1,226 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Retry policy of outbound calls (`BlocklessConfig.retry`); http requests and s3 configs may override it.
+/// The default policy makes a single attempt.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetryPolicy {
+  /// total number of attempts, including the first one
+  pub max_attempts: u32,
+  /// delay before the first retry in milliseconds
+  pub initial_backoff: u64,
+  /// upper bound of the delay between attempts in milliseconds
+  pub max_backoff: u64,
+  /// factor applied to the delay after every attempt
+  pub multiplier: f64,
+  /// randomize the delay between zero and the computed backoff ("full jitter")
+  pub jitter: bool,
+  /// response status codes that are retried
+  pub retryable_status: Vec<u16>,
+  /// only retry idempotent calls (e.g. never retry a `POST`)
+  pub idempotent_only: bool,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    RetryPolicy {
+      max_attempts: 1,
+      initial_backoff: 100,
+      max_backoff: 10_000,
+      multiplier: 2.0,
+      jitter: true,
+      retryable_status: vec![408, 429, 500, 502, 503, 504],
+      idempotent_only: true,
+    }
+  }
+}
+
+impl RetryPolicy {
+  pub fn is_retryable_status(&self, status: u16) -> bool {
+    self.retryable_status.contains(&status)
+  }
+
+  /// Delay after the given (1-based) failed attempt.
+  pub fn backoff(&self, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(64) as i32;
+    let backoff = (self.initial_backoff as f64 * self.multiplier.max(1.0).powi(exponent))
+      .min(self.max_backoff as f64) as u64;
+    let backoff = if self.jitter { random_u64() % backoff.saturating_add(1) } else { backoff };
+    Duration::from_millis(backoff)
+  }
+
+  /// Caps a guest-supplied policy at the runtime policy: no more attempts and no longer delays.
+  pub fn limited_to(&self, limits: &RetryPolicy) -> RetryPolicy {
+    RetryPolicy {
+      max_attempts: self.max_attempts.min(limits.max_attempts),
+      initial_backoff: self.initial_backoff.min(limits.max_backoff),
+      max_backoff: self.max_backoff.min(limits.max_backoff),
+      ..self.clone()
+    }
+  }
+
+  /// Runs `attempt` until it succeeds, `is_retryable` rejects the result or the attempts are exhausted.
+  /// Returns the last result together with the number of attempts made.
+  pub async fn run<T, E, Fut>(
+    &self,
+    idempotent: bool,
+    attempt: impl FnMut() -> Fut,
+    is_retryable: impl Fn(&Result<T, E>) -> bool,
+  ) -> (Result<T, E>, u32)
+  where
+    Fut: Future<Output = Result<T, E>>,
+  {
+    self.run_charged(idempotent, attempt, is_retryable, || true).await
+  }
+
+  /// Like `run`, but every retry is charged first (e.g. against the runtime quotas);
+  /// when `charge_retry` refuses, the last result is returned.
+  pub async fn run_charged<T, E, Fut>(
+    &self,
+    idempotent: bool,
+    mut attempt: impl FnMut() -> Fut,
+    is_retryable: impl Fn(&Result<T, E>) -> bool,
+    mut charge_retry: impl FnMut() -> bool,
+  ) -> (Result<T, E>, u32)
+  where
+    Fut: Future<Output = Result<T, E>>,
+  {
+    let max_attempts = if idempotent || !self.idempotent_only { self.max_attempts.max(1) } else { 1 };
+    let mut attempts = 0;
+    loop {
+      attempts += 1;
+      let result = attempt().await;
+      if attempts >= max_attempts || !is_retryable(&result) {
+        return (result, attempts);
+      }
+      let _ = wasm_timer::Delay::new(self.backoff(attempts)).await;
+      if !charge_retry() {
+        return (result, attempts);
+      }
+    }
+  }
+}
+
+/// Result of an s3 or ipfs host call with the number of network attempts made for it; 0 when nothing
+/// was sent (e.g. the call was rejected or served in memory). The guest callbacks receive the `result`
+/// as before and the attempts as an extra argument.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CallResult<E> {
+  pub result: Result<Vec<u8>, E>,
+  pub attempts: u32,
+}
+
+/// xorshift seeded from the clock; good enough for spreading out retries.
+fn random_u64() -> u64 {
+  static STATE: AtomicU64 = AtomicU64::new(0);
+  let mut x = STATE.load(Ordering::Relaxed);
+  if x == 0 {
+    x = wasm_timer::SystemTime::now()
+      .duration_since(wasm_timer::UNIX_EPOCH)
+      .map(|d| d.as_nanos() as u64)
+      .unwrap_or(0x2545_f491_4f6c_dd1d)
+      | 1;
+  }
+  x ^= x << 13;
+  x ^= x >> 7;
+  x ^= x << 17;
+  STATE.store(x, Ordering::Relaxed);
+  x
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::cell::Cell;
+
+  fn policy(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+      max_attempts,
+      initial_backoff: 1,
+      max_backoff: 5,
+      jitter: false,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_backoff() {
+    let policy = RetryPolicy { jitter: false, ..Default::default() };
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff(4), Duration::from_millis(800));
+    assert_eq!(policy.backoff(100), Duration::from_millis(10_000));
+
+    let policy = RetryPolicy { jitter: true, ..Default::default() };
+    assert!((0..100).all(|_| policy.backoff(3) <= Duration::from_millis(400)));
+
+    let policy = RetryPolicy { initial_backoff: u64::MAX, max_backoff: u64::MAX, jitter: true, ..Default::default() };
+    let _ = policy.backoff(1);
+    assert_eq!(RetryPolicy { jitter: false, ..policy }.backoff(3), Duration::from_millis(u64::MAX));
+  }
+
+  #[test]
+  fn test_limited_to() {
+    let limits = RetryPolicy { max_attempts: 3, max_backoff: 1_000, ..Default::default() };
+    let guest = RetryPolicy {
+      max_attempts: u32::MAX,
+      initial_backoff: u64::MAX,
+      max_backoff: u64::MAX,
+      retryable_status: vec![503],
+      ..Default::default()
+    };
+    let policy = guest.limited_to(&limits);
+    assert_eq!((policy.max_attempts, policy.initial_backoff, policy.max_backoff), (3, 1_000, 1_000));
+    assert_eq!(policy.retryable_status, vec![503]);
+    // stricter guest settings are kept
+    let policy = RetryPolicy { max_attempts: 2, initial_backoff: 10, max_backoff: 50, ..Default::default() }.limited_to(&limits);
+    assert_eq!((policy.max_attempts, policy.initial_backoff, policy.max_backoff), (2, 10, 50));
+  }
+
+  #[test]
+  fn test_policy_serde_defaults() {
+    let policy: RetryPolicy = serde_json::from_str(r#"{"maxAttempts":3,"retryableStatus":[503]}"#).unwrap();
+    assert_eq!(policy.max_attempts, 3);
+    assert!(policy.is_retryable_status(503));
+    assert!(!policy.is_retryable_status(500));
+    assert!(policy.idempotent_only);
+  }
+
+  #[tokio::test]
+  async fn test_run() {
+    let calls = Cell::new(0);
+    let attempt = || {
+      calls.set(calls.get() + 1);
+      let n = calls.get();
+      async move { if n < 3 { Err(n) } else { Ok(n) } }
+    };
+    let (result, attempts) = policy(5).run(true, attempt, |r| r.is_err()).await;
+    assert_eq!((result, attempts), (Ok(3), 3));
+
+    calls.set(0);
+    let (result, attempts) = policy(2).run(true, attempt, |r| r.is_err()).await;
+    assert_eq!((result, attempts), (Err(2), 2));
+
+    // non-idempotent calls are attempted once
+    calls.set(0);
+    let (result, attempts) = policy(5).run(false, attempt, |r| r.is_err()).await;
+    assert_eq!((result, attempts), (Err(1), 1));
+
+    calls.set(0);
+    let (_, attempts) = RetryPolicy { idempotent_only: false, ..policy(5) }.run(false, attempt, |r| r.is_err()).await;
+    assert_eq!(attempts, 3);
+
+    // the retries stop with the last result once they can no longer be charged
+    calls.set(0);
+    let charges = Cell::new(0);
+    let charge_retry = || {
+      charges.set(charges.get() + 1);
+      charges.get() < 2
+    };
+    let (result, attempts) = policy(5).run_charged(true, attempt, |r| r.is_err(), charge_retry).await;
+    assert_eq!((result, attempts, charges.get()), (Err(2), 2, 2));
+  }
+}
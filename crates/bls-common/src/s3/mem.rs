@@ -0,0 +1,683 @@
+//! In-process S3 backend for `mem://` endpoints, so S3 code can be tested offline.
+//!
+//! Requests are answered the way S3 answers them (status codes, headers and XML documents), hence they
+//! take the same path through `S3Command::exec` as requests to a real endpoint. Buckets live in a store
+//! per endpoint authority (`mem://a` and `mem://b` do not share buckets) and only path-style addressing
+//! is supported. ETags are derived from a SHA-256 of the content instead of MD5.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use quick_xml::escape::escape;
+use super::{xml, S3Response, S3_MIN_PART_SIZE};
+
+/// Scheme of the endpoints served by `MemS3`.
+pub const MEM_SCHEME: &str = "mem://";
+
+pub fn is_mem_endpoint(endpoint: &str) -> bool {
+  endpoint.get(..MEM_SCHEME.len()).is_some_and(|scheme| scheme.eq_ignore_ascii_case(MEM_SCHEME))
+}
+
+#[derive(Debug, Clone, Default)]
+struct Object {
+  content: Vec<u8>,
+  e_tag: String,
+  last_modified: u64,
+  content_type: Option<String>,
+  cache_control: Option<String>,
+  content_disposition: Option<String>,
+  metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+struct Upload {
+  key: String,
+  object: Object,
+  parts: BTreeMap<u32, (String, Vec<u8>)>,
+}
+
+#[derive(Debug, Default)]
+struct Bucket {
+  created: u64,
+  objects: BTreeMap<String, Object>,
+  uploads: HashMap<String, Upload>,
+}
+
+#[derive(Debug, Default)]
+struct Store {
+  buckets: BTreeMap<String, Bucket>,
+  next_id: u64,
+}
+
+/// The buckets of all `mem://` endpoints; clones share the data.
+#[derive(Debug, Clone, Default)]
+pub struct MemS3 {
+  stores: Arc<Mutex<HashMap<String, Store>>>,
+}
+
+/// Error document of a failed request.
+struct Error {
+  status: u16,
+  code: &'static str,
+  message: &'static str,
+  resource: String,
+}
+
+fn error(status: u16, code: &'static str, message: &'static str, resource: &str) -> Error {
+  Error { status, code, message, resource: resource.to_string() }
+}
+
+/// A request split into the parts the operations are selected by.
+struct Request<'a> {
+  method: &'a http::Method,
+  bucket: String,
+  key: String,
+  query: HashMap<String, String>,
+  headers: &'a http::HeaderMap,
+  body: &'a [u8],
+}
+
+impl Request<'_> {
+  fn header(&self, name: &str) -> Option<String> {
+    self.headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string)
+  }
+
+  fn resource(&self) -> String {
+    match self.key.is_empty() {
+      true => format!("/{}", self.bucket),
+      false => format!("/{}/{}", self.bucket, self.key),
+    }
+  }
+}
+
+/// Response under construction.
+#[derive(Default)]
+struct Reply {
+  status: u16,
+  headers: HashMap<String, String>,
+  body: Vec<u8>,
+}
+
+impl Reply {
+  fn status(status: u16) -> Self {
+    Reply { status, ..Default::default() }
+  }
+
+  fn xml(body: String) -> Self {
+    Reply::status(200).header("content-type", "application/xml").body(body.into_bytes())
+  }
+
+  fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+    self.headers.insert(name.to_string(), value.into());
+    self
+  }
+
+  fn body(mut self, body: Vec<u8>) -> Self {
+    self.body = body;
+    self
+  }
+}
+
+impl MemS3 {
+  /// Answers a (path-style) request to a `mem://` endpoint.
+  pub fn handle(&self, request: &http::Request<Vec<u8>>) -> S3Response {
+    let uri = request.uri();
+    let namespace = uri.authority().map(|authority| authority.as_str().to_ascii_lowercase()).unwrap_or_default();
+    let path = uri.path().trim_start_matches('/');
+    let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+    let query = uri
+      .query()
+      .unwrap_or_default()
+      .split('&')
+      .filter(|pair| !pair.is_empty())
+      .map(|pair| {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (uri_decode(name), uri_decode(value))
+      })
+      .collect();
+    let request = Request {
+      method: request.method(),
+      bucket: uri_decode(bucket),
+      key: uri_decode(key),
+      query,
+      headers: request.headers(),
+      body: request.body(),
+    };
+
+    let mut stores = self.stores.lock().unwrap();
+    let store = stores.entry(namespace).or_default();
+    store.next_id += 1;
+    let request_id = format!("{:016X}", store.next_id);
+    let reply = store.dispatch(&request, now()).unwrap_or_else(|e| {
+      let body = match request.method == http::Method::HEAD {
+        // responses to HEAD requests carry no body
+        true => vec![],
+        false => format!(
+          "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{}</Code><Message>{}</Message>\
+            <Resource>{}</Resource><RequestId>{}</RequestId></Error>",
+          e.code, e.message, escape(&e.resource), request_id
+        ).into_bytes(),
+      };
+      Reply::status(e.status).header("content-type", "application/xml").body(body)
+    });
+
+    let mut headers = reply.headers;
+    headers.insert("x-amz-request-id".into(), request_id);
+    if !headers.contains_key("content-length") {
+      headers.insert("content-length".into(), reply.body.len().to_string());
+    }
+    S3Response { bytes: reply.body, status_code: reply.status, headers, attempts: 0 }
+  }
+}
+
+impl Store {
+  fn dispatch(&mut self, request: &Request, now: u64) -> Result<Reply, Error> {
+    let query = |name: &str| request.query.contains_key(name);
+    match (request.method.as_str(), request.bucket.is_empty(), request.key.is_empty()) {
+      ("GET", true, _) => Ok(self.list_buckets()),
+      ("PUT", false, true) => self.create_bucket(request, now),
+      ("DELETE", false, true) => self.delete_bucket(request),
+      ("HEAD", false, true) => self.bucket(request).map(|_| Reply::status(200)),
+      ("GET", false, true) => self.list_objects(request),
+      ("POST", false, true) if query("delete") => self.delete_objects(request),
+      ("PUT", false, false) if query("uploadId") => self.upload_part(request),
+      ("PUT", false, false) if request.headers.contains_key("x-amz-copy-source") => self.copy_object(request, now),
+      ("PUT", false, false) => self.put_object(request, now),
+      ("GET", false, false) | ("HEAD", false, false) => self.get_object(request),
+      ("DELETE", false, false) if query("uploadId") => self.abort_multipart_upload(request),
+      ("DELETE", false, false) => self.delete_object(request),
+      ("POST", false, false) if query("uploads") => self.create_multipart_upload(request),
+      ("POST", false, false) if query("uploadId") => self.complete_multipart_upload(request, now),
+      _ => Err(error(501, "NotImplemented", "The operation is not supported by the in-memory backend.", &request.resource())),
+    }
+  }
+
+  fn bucket(&mut self, request: &Request) -> Result<&mut Bucket, Error> {
+    let resource = request.resource();
+    self
+      .buckets
+      .get_mut(&request.bucket)
+      .ok_or_else(|| error(404, "NoSuchBucket", "The specified bucket does not exist.", &resource))
+  }
+
+  fn list_buckets(&self) -> Reply {
+    let buckets: String = self
+      .buckets
+      .iter()
+      .map(|(name, bucket)| format!(
+        "<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>",
+        escape(name), iso_time(bucket.created)
+      ))
+      .collect();
+    Reply::xml(format!(
+      "<ListAllMyBucketsResult><Owner><ID>mem</ID><DisplayName>mem</DisplayName></Owner>\
+        <Buckets>{}</Buckets></ListAllMyBucketsResult>",
+      buckets
+    ))
+  }
+
+  fn create_bucket(&mut self, request: &Request, now: u64) -> Result<Reply, Error> {
+    if self.buckets.contains_key(&request.bucket) {
+      return Err(error(409, "BucketAlreadyOwnedByYou", "The bucket already exists and is owned by you.", &request.resource()));
+    }
+    self.buckets.insert(request.bucket.clone(), Bucket { created: now, ..Default::default() });
+    Ok(Reply::status(200).header("location", format!("/{}", request.bucket)))
+  }
+
+  fn delete_bucket(&mut self, request: &Request) -> Result<Reply, Error> {
+    if !self.bucket(request)?.objects.is_empty() {
+      return Err(error(409, "BucketNotEmpty", "The bucket you tried to delete is not empty.", &request.resource()));
+    }
+    self.buckets.remove(&request.bucket);
+    Ok(Reply::status(204))
+  }
+
+  fn list_objects(&mut self, request: &Request) -> Result<Reply, Error> {
+    let param = |name: &str| request.query.get(name).filter(|value| !value.is_empty()).cloned();
+    if param("list-type").as_deref() != Some("2") {
+      return Err(error(501, "NotImplemented", "Only ListObjectsV2 is supported.", &request.resource()));
+    }
+    let prefix = param("prefix").unwrap_or_default();
+    let delimiter = param("delimiter");
+    let max_keys: usize = param("max-keys").and_then(|max| max.parse().ok()).unwrap_or(1000);
+    let token = param("continuation-token");
+    let bucket = self.bucket(request)?;
+
+    // keys are visited in order; keys below a delimiter are rolled up into their common prefix
+    let mut contents = vec![];
+    let mut common_prefixes: Vec<String> = vec![];
+    let mut last = None;
+    let mut truncated = false;
+    for (key, object) in bucket.objects.range(prefix.clone()..) {
+      if !key.starts_with(&prefix) {
+        break;
+      }
+      if let Some(token) = &token {
+        if key <= token || (common_prefix_token(token, &delimiter) && key.starts_with(token.as_str())) {
+          continue;
+        }
+      }
+      let rolled_up = delimiter.as_ref().and_then(|delimiter| {
+        key[prefix.len()..].find(delimiter.as_str()).map(|idx| key[..prefix.len() + idx + delimiter.len()].to_string())
+      });
+      if rolled_up.is_some() && common_prefixes.last() == rolled_up.as_ref() {
+        continue;
+      }
+      if contents.len() + common_prefixes.len() == max_keys {
+        truncated = true;
+        break;
+      }
+      match rolled_up {
+        Some(common_prefix) => {
+          last = Some(common_prefix.clone());
+          common_prefixes.push(common_prefix);
+        }
+        None => {
+          last = Some(key.clone());
+          contents.push((key, object));
+        }
+      }
+    }
+
+    let optional = |name: &str, value: &Option<String>| {
+      value.as_ref().map(|value| format!("<{0}>{1}</{0}>", name, escape(value))).unwrap_or_default()
+    };
+    let contents: String = contents
+      .iter()
+      .map(|(key, object)| format!(
+        "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size>\
+          <StorageClass>STANDARD</StorageClass></Contents>",
+        escape(key.as_str()), iso_time(object.last_modified), escape(&object.e_tag), object.content.len()
+      ))
+      .collect();
+    let common_prefixes: String = common_prefixes
+      .iter()
+      .map(|prefix| format!("<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>", escape(prefix)))
+      .collect();
+    let key_count = contents.matches("<Contents>").count() + common_prefixes.matches("<CommonPrefixes>").count();
+    Ok(Reply::xml(format!(
+      "<ListBucketResult><Name>{}</Name><Prefix>{}</Prefix>{}<MaxKeys>{}</MaxKeys><KeyCount>{}</KeyCount>\
+        <IsTruncated>{}</IsTruncated>{}{}{}{}</ListBucketResult>",
+      escape(&request.bucket), escape(&prefix), optional("Delimiter", &delimiter), max_keys, key_count, truncated,
+      optional("ContinuationToken", &token), optional("NextContinuationToken", &last.filter(|_| truncated)),
+      contents, common_prefixes
+    )))
+  }
+
+  fn put_object(&mut self, request: &Request, now: u64) -> Result<Reply, Error> {
+    let object = Object {
+      content: request.body.to_vec(),
+      e_tag: e_tag(request.body),
+      last_modified: now,
+      ..object_headers(request)
+    };
+    let e_tag = object.e_tag.clone();
+    self.bucket(request)?.objects.insert(request.key.clone(), object);
+    Ok(Reply::status(200).header("etag", e_tag))
+  }
+
+  fn get_object(&mut self, request: &Request) -> Result<Reply, Error> {
+    let resource = request.resource();
+    let object = self
+      .bucket(request)?
+      .objects
+      .get(&request.key)
+      .ok_or_else(|| error(404, "NoSuchKey", "The specified key does not exist.", &resource))?;
+
+    if let Some(if_match) = request.header("if-match") {
+      if !e_tag_matches(&if_match, &object.e_tag) {
+        return Err(error(412, "PreconditionFailed", "At least one of the pre-conditions you specified did not hold.", &resource));
+      }
+    }
+    let mut reply = Reply::status(200)
+      .header("etag", object.e_tag.clone())
+      .header("last-modified", http_time(object.last_modified))
+      .header("accept-ranges", "bytes")
+      .header("content-type", object.content_type.clone().unwrap_or_else(|| "binary/octet-stream".into()));
+    if let Some(cache_control) = &object.cache_control {
+      reply = reply.header("cache-control", cache_control.clone());
+    }
+    if let Some(content_disposition) = &object.content_disposition {
+      reply = reply.header("content-disposition", content_disposition.clone());
+    }
+    for (name, value) in &object.metadata {
+      reply = reply.header(&format!("x-amz-meta-{}", name), value.clone());
+    }
+    if let Some(if_none_match) = request.header("if-none-match") {
+      if e_tag_matches(&if_none_match, &object.e_tag) {
+        reply.status = 304;
+        return Ok(reply);
+      }
+    }
+
+    let size = object.content.len() as u64;
+    let (status, start, end) = match request.header("range") {
+      Some(range) => match parse_range(&range, size) {
+        Some((start, end)) => (206, start, end),
+        None => return Err(error(416, "InvalidRange", "The requested range is not satisfiable.", &resource)),
+      },
+      None => (200, 0, size),
+    };
+    if status == 206 {
+      reply = reply.header("content-range", format!("bytes {}-{}/{}", start, end - 1, size));
+    }
+    reply.status = status;
+    let body = object.content[start as usize..end as usize].to_vec();
+    reply = reply.header("content-length", body.len().to_string());
+    if request.method == http::Method::GET {
+      reply.body = body;
+    }
+    Ok(reply)
+  }
+
+  fn delete_object(&mut self, request: &Request) -> Result<Reply, Error> {
+    // deleting a missing key succeeds as well
+    self.bucket(request)?.objects.remove(&request.key);
+    Ok(Reply::status(204))
+  }
+
+  fn copy_object(&mut self, request: &Request, now: u64) -> Result<Reply, Error> {
+    let source = uri_decode(&request.header("x-amz-copy-source").unwrap_or_default());
+    let source = source.trim_start_matches('/');
+    let (source_bucket, source_key) = source.split_once('/').unwrap_or((source, ""));
+    let resource = format!("/{}", source);
+    let source = self
+      .buckets
+      .get(source_bucket)
+      .ok_or_else(|| error(404, "NoSuchBucket", "The specified bucket does not exist.", &resource))?
+      .objects
+      .get(source_key)
+      .ok_or_else(|| error(404, "NoSuchKey", "The specified key does not exist.", &resource))?;
+
+    let mut object = match request.header("x-amz-metadata-directive").as_deref() {
+      Some("REPLACE") => Object { content: source.content.clone(), e_tag: source.e_tag.clone(), ..object_headers(request) },
+      _ => source.clone(),
+    };
+    object.last_modified = now;
+    let e_tag = object.e_tag.clone();
+    self.bucket(request)?.objects.insert(request.key.clone(), object);
+    Ok(Reply::xml(format!(
+      "<CopyObjectResult><LastModified>{}</LastModified><ETag>{}</ETag></CopyObjectResult>",
+      iso_time(now), escape(&e_tag)
+    )))
+  }
+
+  fn delete_objects(&mut self, request: &Request) -> Result<Reply, Error> {
+    let resource = request.resource();
+    let checksum = base64::engine::general_purpose::STANDARD.encode(Sha256::digest(request.body));
+    if request.header("x-amz-checksum-sha256").is_some_and(|expected| expected != checksum) {
+      return Err(error(400, "BadDigest", "The SHA256 you specified did not match the calculated checksum.", &resource));
+    }
+    let delete: xml::DeleteRequest = xml::parse(request.body)
+      .map_err(|_| error(400, "MalformedXML", "The XML you provided was not well-formed.", &resource))?;
+    let bucket = self.bucket(request)?;
+    let mut deleted = String::new();
+    for key in delete.keys() {
+      // like S3, keys that do not exist are reported as deleted
+      bucket.objects.remove(key);
+      if !delete.quiet() {
+        deleted.push_str(&format!("<Deleted><Key>{}</Key></Deleted>", escape(key)));
+      }
+    }
+    Ok(Reply::xml(format!("<DeleteResult>{}</DeleteResult>", deleted)))
+  }
+
+  fn create_multipart_upload(&mut self, request: &Request) -> Result<Reply, Error> {
+    let object = object_headers(request);
+    // ids are never reused: `next_id` is unique per request
+    let upload_id = format!("mem-upload-{}", self.next_id);
+    let bucket = self.bucket(request)?;
+    bucket.uploads.insert(upload_id.clone(), Upload { key: request.key.clone(), object, parts: BTreeMap::new() });
+    Ok(Reply::xml(format!(
+      "<InitiateMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId></InitiateMultipartUploadResult>",
+      escape(&request.bucket), escape(&request.key), upload_id
+    )))
+  }
+
+  fn upload(&mut self, request: &Request) -> Result<&mut Upload, Error> {
+    let resource = request.resource();
+    let upload_id = request.query.get("uploadId").cloned().unwrap_or_default();
+    self
+      .bucket(request)?
+      .uploads
+      .get_mut(&upload_id)
+      .filter(|upload| upload.key == request.key)
+      .ok_or_else(|| error(404, "NoSuchUpload", "The specified multipart upload does not exist.", &resource))
+  }
+
+  fn upload_part(&mut self, request: &Request) -> Result<Reply, Error> {
+    let part_number = request
+      .query
+      .get("partNumber")
+      .and_then(|number| number.parse::<u32>().ok())
+      .filter(|number| (1..=10000).contains(number))
+      .ok_or_else(|| error(400, "InvalidArgument", "Part number must be an integer between 1 and 10000.", &request.resource()))?;
+    let e_tag = e_tag(request.body);
+    self.upload(request)?.parts.insert(part_number, (e_tag.clone(), request.body.to_vec()));
+    Ok(Reply::status(200).header("etag", e_tag))
+  }
+
+  fn complete_multipart_upload(&mut self, request: &Request, now: u64) -> Result<Reply, Error> {
+    let resource = request.resource();
+    let complete: xml::CompleteMultipartUploadRequest = xml::parse(request.body)
+      .map_err(|_| error(400, "MalformedXML", "The XML you provided was not well-formed.", &resource))?;
+    let upload = self.upload(request)?;
+
+    let requested = complete.parts();
+    if requested.is_empty() || requested.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+      return Err(error(400, "InvalidPartOrder", "The list of parts was not in ascending order.", &resource));
+    }
+    let mut content = vec![];
+    let mut digest = Sha256::new();
+    for (i, (part_number, e_tag)) in requested.iter().enumerate() {
+      let (uploaded_e_tag, part) = upload
+        .parts
+        .get(part_number)
+        .filter(|(uploaded_e_tag, _)| e_tag_matches(e_tag, uploaded_e_tag))
+        .ok_or_else(|| error(400, "InvalidPart", "One or more of the specified parts could not be found.", &resource))?;
+      if i + 1 < requested.len() && part.len() < S3_MIN_PART_SIZE {
+        return Err(error(400, "EntityTooSmall", "Your proposed upload is smaller than the minimum allowed object size.", &resource));
+      }
+      digest.update(uploaded_e_tag.as_bytes());
+      content.extend_from_slice(part);
+    }
+
+    let upload_id = request.query.get("uploadId").cloned().unwrap_or_default();
+    let upload = self.bucket(request)?.uploads.remove(&upload_id).unwrap_or_default();
+    let e_tag = format!("\"{}-{}\"", hex(&digest.finalize()[..16]), requested.len());
+    let object = Object { content, e_tag: e_tag.clone(), last_modified: now, ..upload.object };
+    self.bucket(request)?.objects.insert(request.key.clone(), object);
+    Ok(Reply::xml(format!(
+      "<CompleteMultipartUploadResult><Location>{}</Location><Bucket>{}</Bucket><Key>{}</Key><ETag>{}</ETag>\
+        </CompleteMultipartUploadResult>",
+      escape(&resource), escape(&request.bucket), escape(&request.key), escape(&e_tag)
+    )))
+  }
+
+  fn abort_multipart_upload(&mut self, request: &Request) -> Result<Reply, Error> {
+    self.upload(request)?;
+    let upload_id = request.query.get("uploadId").cloned().unwrap_or_default();
+    self.bucket(request)?.uploads.remove(&upload_id);
+    Ok(Reply::status(204))
+  }
+}
+
+/// Content headers and user metadata of a put (or replacing copy).
+fn object_headers(request: &Request) -> Object {
+  Object {
+    content_type: request.header("content-type"),
+    cache_control: request.header("cache-control"),
+    content_disposition: request.header("content-disposition"),
+    metadata: request
+      .headers
+      .iter()
+      .filter_map(|(name, value)| Some((name.as_str().strip_prefix("x-amz-meta-")?.to_string(), value.to_str().ok()?.to_string())))
+      .collect(),
+    ..Default::default()
+  }
+}
+
+/// A continuation token naming a common prefix skips every key below it.
+fn common_prefix_token(token: &str, delimiter: &Option<String>) -> bool {
+  delimiter.as_ref().is_some_and(|delimiter| token.ends_with(delimiter.as_str()))
+}
+
+fn e_tag(content: &[u8]) -> String {
+  format!("\"{}\"", hex(&Sha256::digest(content)[..16]))
+}
+
+/// Compares an `If-Match`/`If-None-Match` value (possibly a list, or `*`) with an ETag.
+fn e_tag_matches(condition: &str, e_tag: &str) -> bool {
+  condition
+    .split(',')
+    .map(|candidate| candidate.trim().trim_start_matches("W/"))
+    .any(|candidate| candidate == "*" || candidate.trim_matches('"') == e_tag.trim_matches('"'))
+}
+
+/// Resolves `bytes=start-end`, `bytes=start-` or `bytes=-suffix` into `start..end`.
+fn parse_range(range: &str, size: u64) -> Option<(u64, u64)> {
+  let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+  let (start, end) = match (start.parse::<u64>().ok(), end.parse::<u64>().ok()) {
+    (Some(start), Some(end)) if start <= end => (start, end.saturating_add(1).min(size)),
+    (Some(start), None) if end.is_empty() => (start, size),
+    (None, Some(suffix)) if start.is_empty() && suffix > 0 => (size.saturating_sub(suffix), size),
+    _ => return None,
+  };
+  Some((start, end)).filter(|(start, _)| *start < size)
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn uri_decode(value: &str) -> String {
+  let bytes = value.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let escaped = bytes.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+    match (bytes[i], escaped) {
+      (b'%', Some(byte)) => {
+        decoded.push(byte);
+        i += 3;
+      }
+      (byte, _) => {
+        decoded.push(byte);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn now() -> u64 {
+  wasm_timer::SystemTime::now()
+    .duration_since(wasm_timer::UNIX_EPOCH)
+    .map(|elapsed| elapsed.as_secs())
+    .unwrap_or_default()
+}
+
+/// Date and time of `secs` since the unix epoch: (year, month, day, hours, minutes, seconds, weekday).
+fn civil_time(secs: u64) -> (i64, u64, u64, u64, u64, u64, usize) {
+  let days = (secs / 86400) as i64;
+  let time = secs % 86400;
+  // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = (doy - (153 * mp + 2) / 5 + 1) as u64;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 } as u64;
+  let year = yoe + era * 400 + i64::from(month <= 2);
+  // 1970-01-01 was a thursday
+  let weekday = ((days + 4) % 7) as usize;
+  (year, month, day, time / 3600, time % 3600 / 60, time % 60, weekday)
+}
+
+/// `2023-09-01T10:00:00.000Z`, as used in XML documents.
+fn iso_time(secs: u64) -> String {
+  let (year, month, day, hours, minutes, seconds, _) = civil_time(secs);
+  format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.000Z", year, month, day, hours, minutes, seconds)
+}
+
+/// `Fri, 01 Sep 2023 10:00:00 GMT`, as used in headers.
+fn http_time(secs: u64) -> String {
+  const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+  const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+  let (year, month, day, hours, minutes, seconds, weekday) = civil_time(secs);
+  format!(
+    "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+    WEEKDAYS[weekday], day, MONTHS[month as usize - 1], year, hours, minutes, seconds
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_time_formats() {
+    assert_eq!(iso_time(0), "1970-01-01T00:00:00.000Z");
+    assert_eq!(http_time(1369353600), "Fri, 24 May 2013 00:00:00 GMT");
+    assert_eq!(iso_time(1709210096), "2024-02-29T12:34:56.000Z");
+  }
+
+  #[test]
+  fn test_ranges() {
+    assert_eq!(parse_range("bytes=0-9", 100), Some((0, 10)));
+    assert_eq!(parse_range("bytes=90-200", 100), Some((90, 100)));
+    assert_eq!(parse_range("bytes=10-", 100), Some((10, 100)));
+    assert_eq!(parse_range("bytes=0-18446744073709551615", 100), Some((0, 100)));
+    assert_eq!(parse_range("bytes=-10", 100), Some((90, 100)));
+    assert_eq!(parse_range("bytes=100-", 100), None);
+    assert_eq!(parse_range("bytes=5-1", 100), None);
+    assert_eq!(parse_range("items=0-1", 100), None);
+  }
+
+  #[test]
+  fn test_e_tag_conditions() {
+    assert!(e_tag_matches("\"abc\"", "\"abc\""));
+    assert!(e_tag_matches("abc", "\"abc\""));
+    assert!(e_tag_matches("\"x\", W/\"abc\"", "\"abc\""));
+    assert!(e_tag_matches("*", "\"abc\""));
+    assert!(!e_tag_matches("\"abd\"", "\"abc\""));
+  }
+
+  #[test]
+  fn test_namespaces() {
+    let mem = MemS3::default();
+    let request = |method: &str, uri: &str| http::Request::builder().method(method).uri(uri).body(vec![]).unwrap();
+    assert_eq!(mem.handle(&request("PUT", "mem://a/bucket")).status_code, 200);
+    assert_eq!(mem.handle(&request("PUT", "mem://a/bucket")).status_code, 409);
+    assert_eq!(mem.handle(&request("HEAD", "mem://a/bucket")).status_code, 200);
+    let missing = mem.handle(&request("HEAD", "mem://b/bucket"));
+    assert_eq!(missing.status_code, 404);
+    assert!(missing.bytes.is_empty());
+    assert_eq!(mem.handle(&request("PATCH", "mem://a/bucket")).status_code, 501);
+  }
+
+  #[test]
+  fn test_concurrent_uploads() {
+    let mem = MemS3::default();
+    let request = |method: &str, uri: &str| http::Request::builder().method(method).uri(uri).body(vec![]).unwrap();
+    let create = |key: &str| {
+      let response = mem.handle(&request("POST", &format!("mem://a/bucket/{}?uploads", key)));
+      let body = String::from_utf8(response.bytes).unwrap();
+      body.split("<UploadId>").nth(1).and_then(|rest| rest.split("</UploadId>").next()).unwrap().to_string()
+    };
+    assert_eq!(mem.handle(&request("PUT", "mem://a/bucket")).status_code, 200);
+    let a = create("a.txt");
+    let b = create("b.txt");
+    assert_eq!(mem.handle(&request("DELETE", &format!("mem://a/bucket/a.txt?uploadId={}", a))).status_code, 204);
+    let c = create("c.txt");
+    assert!(a != b && b != c && a != c);
+
+    // b is still in flight next to c
+    for (key, upload_id) in [("b.txt", &b), ("c.txt", &c)] {
+      let uri = format!("mem://a/bucket/{}?partNumber=1&uploadId={}", key, upload_id);
+      assert_eq!(mem.handle(&request("PUT", &uri)).status_code, 200);
+    }
+  }
+}
@@ -0,0 +1,313 @@
+//! XML documents of the S3 REST API; converted into the (json) response types handed to the guest.
+use serde::Deserialize;
+use super::{
+  S3Bucket, S3ServiceError, S3CompleteMultipartUploadResponse, S3CompletedPart, S3CopyObjectResponse, S3DeleteObjectsError,
+  S3DeleteObjectsResponse, S3ListBucketsResponse, S3ListItemResponse, S3ListItemResponseContent, S3MultipartUpload,
+  S3Owner,
+};
+
+// https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectsV2.html
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct ListBucketResult {
+  name: String,
+  prefix: Option<String>,
+  delimiter: Option<String>,
+  max_keys: Option<u32>,
+  key_count: Option<u32>,
+  is_truncated: bool,
+  continuation_token: Option<String>,
+  next_continuation_token: Option<String>,
+  #[serde(default)]
+  contents: Vec<Contents>,
+  #[serde(default)]
+  common_prefixes: Vec<CommonPrefix>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Contents {
+  key: String,
+  last_modified: String,
+  #[serde(rename = "ETag")]
+  e_tag: Option<String>,
+  size: u64,
+  storage_class: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CommonPrefix {
+  prefix: String,
+}
+
+/// Empty elements (e.g. `<Prefix></Prefix>`) are treated as not set.
+fn non_empty(value: Option<String>) -> Option<String> {
+  value.filter(|value| !value.is_empty())
+}
+
+impl From<ListBucketResult> for S3ListItemResponse {
+  fn from(result: ListBucketResult) -> Self {
+    S3ListItemResponse {
+      name: result.name,
+      is_truncated: result.is_truncated,
+      prefix: non_empty(result.prefix),
+      delimiter: non_empty(result.delimiter),
+      max_keys: result.max_keys,
+      key_count: result.key_count,
+      continuation_token: non_empty(result.continuation_token),
+      next_continuation_token: non_empty(result.next_continuation_token),
+      contents: result.contents.into_iter().map(Into::into).collect(),
+      common_prefixes: result.common_prefixes.into_iter().map(|prefix| prefix.prefix).collect(),
+    }
+  }
+}
+
+impl From<Contents> for S3ListItemResponseContent {
+  fn from(contents: Contents) -> Self {
+    S3ListItemResponseContent {
+      last_modified: contents.last_modified,
+      e_tag: contents.e_tag,
+      storage_class: contents.storage_class,
+      key: contents.key,
+      size: contents.size,
+    }
+  }
+}
+
+// https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateMultipartUpload.html
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct InitiateMultipartUploadResult {
+  bucket: String,
+  key: String,
+  upload_id: String,
+}
+
+impl From<InitiateMultipartUploadResult> for S3MultipartUpload {
+  fn from(result: InitiateMultipartUploadResult) -> Self {
+    S3MultipartUpload { bucket_name: result.bucket, path: result.key, upload_id: result.upload_id }
+  }
+}
+
+// https://docs.aws.amazon.com/AmazonS3/latest/API/API_CompleteMultipartUpload.html
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct CompleteMultipartUploadResult {
+  location: Option<String>,
+  bucket: String,
+  key: String,
+  #[serde(rename = "ETag")]
+  e_tag: Option<String>,
+}
+
+impl From<CompleteMultipartUploadResult> for S3CompleteMultipartUploadResponse {
+  fn from(result: CompleteMultipartUploadResult) -> Self {
+    S3CompleteMultipartUploadResponse {
+      location: result.location,
+      bucket_name: result.bucket,
+      path: result.key,
+      e_tag: result.e_tag,
+    }
+  }
+}
+
+/// Request body of CompleteMultipartUpload; parts are listed in ascending part number order.
+pub(super) fn complete_multipart_upload(parts: &[S3CompletedPart]) -> String {
+  let mut parts = parts.to_vec();
+  parts.sort_by_key(|part| part.part_number);
+  let parts: String = parts
+    .iter()
+    .map(|part| format!(
+      "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+      part.part_number, quick_xml::escape::escape(&part.e_tag)
+    ))
+    .collect();
+  format!(
+    r#"<CompleteMultipartUpload xmlns="http://s3.amazonaws.com/doc/2006-03-01/">{}</CompleteMultipartUpload>"#,
+    parts
+  )
+}
+
+// https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListBuckets.html
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct ListAllMyBucketsResult {
+  #[serde(default)]
+  buckets: Buckets,
+  owner: Option<Owner>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "PascalCase")]
+struct Buckets {
+  #[serde(default)]
+  bucket: Vec<Bucket>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Bucket {
+  name: String,
+  creation_date: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Owner {
+  #[serde(rename = "ID")]
+  id: String,
+  display_name: Option<String>,
+}
+
+impl From<ListAllMyBucketsResult> for S3ListBucketsResponse {
+  fn from(result: ListAllMyBucketsResult) -> Self {
+    S3ListBucketsResponse {
+      buckets: result
+        .buckets
+        .bucket
+        .into_iter()
+        .map(|bucket| S3Bucket { name: bucket.name, creation_date: bucket.creation_date })
+        .collect(),
+      owner: result.owner.map(|owner| S3Owner { id: owner.id, display_name: non_empty(owner.display_name) }),
+    }
+  }
+}
+
+// https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct CopyObjectResult {
+  #[serde(rename = "ETag")]
+  e_tag: String,
+  last_modified: String,
+}
+
+impl From<CopyObjectResult> for S3CopyObjectResponse {
+  fn from(result: CopyObjectResult) -> Self {
+    S3CopyObjectResponse { e_tag: result.e_tag, last_modified: result.last_modified }
+  }
+}
+
+// https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObjects.html
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct DeleteResult {
+  #[serde(default)]
+  deleted: Vec<Deleted>,
+  #[serde(default)]
+  error: Vec<DeleteError>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Deleted {
+  key: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DeleteError {
+  key: String,
+  code: String,
+  message: Option<String>,
+}
+
+impl From<DeleteResult> for S3DeleteObjectsResponse {
+  fn from(result: DeleteResult) -> Self {
+    S3DeleteObjectsResponse {
+      deleted: result.deleted.into_iter().map(|deleted| deleted.key).collect(),
+      errors: result
+        .error
+        .into_iter()
+        .map(|error| S3DeleteObjectsError { path: error.key, code: error.code, message: non_empty(error.message) })
+        .collect(),
+    }
+  }
+}
+
+/// Request body of DeleteObjects.
+pub(super) fn delete_objects(paths: &[String], quiet: bool) -> String {
+  let objects: String = paths
+    .iter()
+    .map(|path| format!("<Object><Key>{}</Key></Object>", quick_xml::escape::escape(path.trim_start_matches('/'))))
+    .collect();
+  format!(
+    r#"<Delete xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><Quiet>{}</Quiet>{}</Delete>"#,
+    quiet, objects
+  )
+}
+
+// https://docs.aws.amazon.com/AmazonS3/latest/API/ErrorResponses.html
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct Error {
+  code: String,
+  message: Option<String>,
+  request_id: Option<String>,
+  resource: Option<String>,
+}
+
+impl From<Error> for S3ServiceError {
+  fn from(error: Error) -> Self {
+    S3ServiceError {
+      status_code: 0,
+      code: error.code,
+      message: non_empty(error.message),
+      request_id: non_empty(error.request_id),
+      resource: non_empty(error.resource),
+    }
+  }
+}
+
+// request documents, as read by the in-memory backend
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct DeleteRequest {
+  quiet: Option<bool>,
+  #[serde(default)]
+  object: Vec<ObjectIdentifier>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ObjectIdentifier {
+  key: String,
+}
+
+impl DeleteRequest {
+  pub(super) fn quiet(&self) -> bool {
+    self.quiet.unwrap_or(false)
+  }
+
+  pub(super) fn keys(&self) -> impl Iterator<Item = &str> {
+    self.object.iter().map(|object| object.key.as_str())
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct CompleteMultipartUploadRequest {
+  #[serde(default)]
+  part: Vec<CompletedPart>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CompletedPart {
+  part_number: u32,
+  #[serde(rename = "ETag")]
+  e_tag: String,
+}
+
+impl CompleteMultipartUploadRequest {
+  /// (part number, etag) in the order listed.
+  pub(super) fn parts(&self) -> Vec<(u32, String)> {
+    self.part.iter().map(|part| (part.part_number, part.e_tag.clone())).collect()
+  }
+}
+
+pub(super) fn parse<T: serde::de::DeserializeOwned>(xml: &[u8]) -> Result<T, &'static str> {
+  let xml = std::str::from_utf8(xml).map_err(|_| "invalid xml response")?;
+  quick_xml::de::from_str(xml).map_err(|_| "failed to parse xml response")
+}
@@ -0,0 +1,29 @@
+/// Clock and timer helpers shared by the extensions.
+use std::future::Future;
+use std::time::Duration;
+
+/// Races the future against a timer; dropping the losing future cancels the in-flight request.
+/// NOTE: `reqwest::Request::timeout` is ignored in wasm, hence the explicit timer.
+pub(crate) async fn with_timeout<T, E>(
+  future: impl Future<Output = Result<T, E>>,
+  timeout: u64,
+  on_timeout: impl FnOnce(u64) -> E,
+) -> Result<T, E> {
+  use futures::future::{select, Either};
+
+  let delay = wasm_timer::Delay::new(Duration::from_millis(timeout));
+  futures::pin_mut!(future, delay);
+  match select(future, delay).await {
+    Either::Left((result, _)) => result,
+    Either::Right(_) => Err(on_timeout(timeout)),
+  }
+}
+
+/// Current unix time in seconds (with sub-second precision); the single clock used by quotas,
+/// retries and the http cache, both natively and in wasm.
+pub fn now_secs() -> f64 {
+  wasm_timer::SystemTime::now()
+    .duration_since(wasm_timer::UNIX_EPOCH)
+    .map(|d| d.as_secs_f64())
+    .unwrap_or_default()
+}
@@ -4,15 +4,28 @@ use std::collections::HashMap;
 use std::cell::{Cell, RefCell};
 use std::fmt::Debug;
 use std::sync::atomic::AtomicU64;
-use bls_common::s3::{S3CreateOpts, S3PutOpts, S3DeleteOpts};
+use bls_common::s3::{
+    S3CreateOpts, S3PutOpts, S3DeleteOpts, S3CreateMultipartUploadOpts, S3UploadPartOpts, S3CompleteMultipartUploadOpts,
+    S3AbortMultipartUploadOpts, S3CompleteMultipartUploadResponse, S3CompletedPart, S3MultipartUpload, S3PresignOpts,
+    S3PresignedUrl, S3HeadObjectOpts, S3ObjectMetadata, S3Response, S3ListBucketsOpts, S3ListBucketsResponse,
+    S3HeadBucketOpts, S3HeadBucketResponse, S3CopyObjectOpts, S3CopyObjectResponse, S3DeleteObjectsOpts,
+    S3DeleteObjectsResponse,
+};
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 use futures::channel::oneshot;
+use futures::{Stream, StreamExt};
 
 use bls_common::{
-    http::{Method, HttpRequest, HttpResponse},
-    s3::{S3Command, S3Config, S3ListOpts, S3GetOpts},
-    ipfs::{IPFSCommand, FilesLsOpts},
+    http::{Method, HttpError, HttpRequest, HttpResponse},
+    quota::QUOTA_EXCEEDED_STATUS,
+    retry::CallResult,
+    s3::{S3Command, S3Config, S3Error, S3ListOpts, S3ListItemResponse, S3GetOpts},
+    ipfs::{
+        IPFSCommand, FilesLsOpts, FilesLsResponse, FilesStatOpts, FilesStatResponse, VersionOpts, VersionResponse,
+        AddOpts, AddFile, AddResponse, LsOpts, LsResponse, PinAddOpts, PinLsOpts, PinLsResponse, PinResponse,
+        PinRmOpts, PinUpdateOpts, PinRemoteAddOpts, PinRemoteLsOpts, PinRemoteLsResponse, PinRemoteRmOpts, RemotePin,
+    },
 };
 
 mod executor;
@@ -30,6 +43,18 @@ extern "C" {
     pub fn http_call(ptr: u32, len: u32, callback_id: u64) -> u32;
 }
 
+#[link(wasm_import_module = "blockless")]
+extern "C" {
+    #[link_name = "http_read_body"]
+    pub fn http_read_body(handle: u32, max_len: u32, callback_id: u64) -> u32;
+}
+
+#[link(wasm_import_module = "blockless")]
+extern "C" {
+    #[link_name = "http_close"]
+    pub fn http_close(handle: u32) -> u32;
+}
+
 #[link(wasm_import_module = "blockless")]
 extern "C" {
     #[link_name = "s3_call"]
@@ -77,39 +102,41 @@ pub unsafe fn dealloc(ptr: *mut u8, size: usize) {
 #[no_mangle]
 pub fn http_callback(result_ptr: usize, callback_id: u64) -> *const u8 {
     let serialized = decode_from_ptr(result_ptr);
-    let http_call_response: Result<Vec<u8>, String> = serde_json::from_slice(&serialized[..]).unwrap(); // TODO: handle error
 
     PENDING_CALLS.with(|calls| {
         if let Some(sender) = calls.borrow_mut().remove(&callback_id) {
-            sender.send(http_call_response).expect("Failed to send http_callback result");
+            sender.send(serialized).expect("Failed to send http_callback result");
         }
     });
     executor::EXECUTOR.with(|e| e.borrow_mut().run());
     0 as *const u8
 }
 
+/// `attempts` is the number of network attempts the runtime made for the call (see `RetryPolicy`);
+/// runtimes that predate it pass no third argument, which reads as 0.
 #[no_mangle]
-pub fn s3_callback(result_ptr: usize, callback_id: u64) -> *const u8 {
+pub fn s3_callback(result_ptr: usize, callback_id: u64, attempts: u32) -> *const u8 {
     let serialized = decode_from_ptr(result_ptr);
-    let s3_call_response: Result<Vec<u8>, String> = serde_json::from_slice(&serialized[..]).unwrap(); // TODO: handle error
+    CALL_ATTEMPTS.with(|calls| calls.borrow_mut().insert(callback_id, attempts));
 
     PENDING_CALLS.with(|calls| {
         if let Some(sender) = calls.borrow_mut().remove(&callback_id) {
-            sender.send(s3_call_response).expect("Failed to send s3_callback result");
+            sender.send(serialized).expect("Failed to send s3_callback result");
         }
     });
     executor::EXECUTOR.with(|e| e.borrow_mut().run());
     0 as *const u8
 }
 
+/// See `s3_callback`.
 #[no_mangle]
-pub fn ipfs_callback(result_ptr: usize, callback_id: u64) -> *const u8 {
+pub fn ipfs_callback(result_ptr: usize, callback_id: u64, attempts: u32) -> *const u8 {
     let serialized = decode_from_ptr(result_ptr);
-    let ipfs_call_response: Result<Vec<u8>, String> = serde_json::from_slice(&serialized[..]).unwrap(); // TODO: handle error
+    CALL_ATTEMPTS.with(|calls| calls.borrow_mut().insert(callback_id, attempts));
 
     PENDING_CALLS.with(|calls| {
         if let Some(sender) = calls.borrow_mut().remove(&callback_id) {
-            sender.send(ipfs_call_response).expect("Failed to send ipfs_callback result");
+            sender.send(serialized).expect("Failed to send ipfs_callback result");
         }
     });
     executor::EXECUTOR.with(|e| e.borrow_mut().run());
@@ -134,46 +161,368 @@ fn decode_from_ptr(result_ptr: usize) -> Vec<u8> {
 static NEXT_CALLBACK_ID: AtomicU64 = AtomicU64::new(0);
 
 // global mutable variables (since this is a single-threaded runtime)
+// NOTE: pending calls receive the raw (serialized) callback result; each `dispatch_*` fn decodes its own error type
 thread_local! {
-    static PENDING_CALLS: RefCell<HashMap<u64, oneshot::Sender<Result<Vec<u8>, String>>>> = RefCell::new(HashMap::new());
+    static PENDING_CALLS: RefCell<HashMap<u64, oneshot::Sender<Vec<u8>>>> = RefCell::new(HashMap::new());
+    /// network attempts reported by the s3 and ipfs callbacks, until the dispatching call picks them up
+    static CALL_ATTEMPTS: RefCell<HashMap<u64, u32>> = RefCell::new(HashMap::new());
+}
+
+/// Decodes the serialized `Result<Vec<u8>, E>` that the host passes to the callbacks.
+fn decode_call_result<E: DeserializeOwned>(serialized: &[u8]) -> Result<Result<Vec<u8>, E>, &'static str> {
+    serde_json::from_slice(serialized).map_err(|_| "Failed to deserialize call result")
+}
+
+/// Decodes the callback result of an s3 or ipfs call, along with the attempts reported by the callback.
+fn decode_counted_result<E: DeserializeOwned + From<&'static str>>(serialized: &[u8], attempts: u32) -> CallResult<E> {
+    let result = decode_call_result::<E>(serialized).unwrap_or_else(|e| Err(e.into()));
+    CallResult { result, attempts }
+}
+
+/// Error of a host call that did not produce a callback result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostCallError {
+    /// rejected by the runtime quotas
+    QuotaExceeded,
+    Failed(&'static str),
+}
+
+impl From<&'static str> for HostCallError {
+    fn from(e: &'static str) -> Self {
+        HostCallError::Failed(e)
+    }
+}
+
+impl From<HostCallError> for &'static str {
+    fn from(e: HostCallError) -> Self {
+        match e {
+            HostCallError::QuotaExceeded => "Quota exceeded",
+            HostCallError::Failed(e) => e,
+        }
+    }
+}
+
+impl From<HostCallError> for HttpError {
+    fn from(e: HostCallError) -> Self {
+        match e {
+            HostCallError::QuotaExceeded => HttpError::QuotaExceeded,
+            HostCallError::Failed(e) => e.into(),
+        }
+    }
 }
 
 pub async fn dispatch_host_call(
     data: impl Serialize,
     host_call_fn: unsafe extern "C" fn(u32, u32, u64) -> u32,
-) -> Result<Vec<u8>, &'static str> {
-    let (sender, receiver) = oneshot::channel();
+) -> Result<Vec<u8>, HostCallError> {
+    let data = serde_json::to_vec(&data).map_err(|_| "Failed to serialize request")?;
 
-    let callback_id = NEXT_CALLBACK_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-    PENDING_CALLS.with(|calls| calls.borrow_mut().insert(callback_id, sender));
+    // Call the FFI function.
+    await_host_callback(|callback_id| unsafe {
+        host_call_fn(data.as_ptr() as u32, data.len() as u32, callback_id)
+    }).await
+}
 
+/// Like `dispatch_host_call`, also picking up the attempts reported by the s3 and ipfs callbacks.
+async fn dispatch_counted_host_call(
+    data: impl Serialize,
+    host_call_fn: unsafe extern "C" fn(u32, u32, u64) -> u32,
+) -> Result<(Vec<u8>, u32), HostCallError> {
     let data = serde_json::to_vec(&data).map_err(|_| "Failed to serialize request")?;
 
-    // Call the FFI function.
-    let result_ptr = unsafe { host_call_fn(data.as_ptr() as u32, data.len() as u32, callback_id) };
+    let id = Cell::new(0);
+    let serialized = await_host_callback(|callback_id| unsafe {
+        id.set(callback_id);
+        host_call_fn(data.as_ptr() as u32, data.len() as u32, callback_id)
+    }).await?;
+    let attempts = CALL_ATTEMPTS.with(|calls| calls.borrow_mut().remove(&id.get())).unwrap_or(0);
+    Ok((serialized, attempts))
+}
+
+/// Registers a pending callback, performs the host call and waits for the host to invoke the callback.
+async fn await_host_callback(host_call: impl FnOnce(u64) -> u32) -> Result<Vec<u8>, HostCallError> {
+    let (sender, receiver) = oneshot::channel();
+
+    let callback_id = NEXT_CALLBACK_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    PENDING_CALLS.with(|calls| calls.borrow_mut().insert(callback_id, sender));
 
     // If early return value is non-zero, an error must have ocurred in host runtime.
-    if result_ptr != 0 {
-        Err("Failed to dispatch the call")?;
+    let status = host_call(callback_id);
+    if status != 0 {
+        PENDING_CALLS.with(|calls| calls.borrow_mut().remove(&callback_id));
+        return Err(match status {
+            QUOTA_EXCEEDED_STATUS => HostCallError::QuotaExceeded,
+            _ => HostCallError::Failed("Failed to dispatch the call"),
+        });
     }
 
     let response = receiver.await
-        .map_err(|_| "Failed to receive response")?
-        .map_err(|_| "Failed to retrieve data")?;
+        .map_err(|_| "Failed to receive response")?;
     Ok(response)
 }
 
-pub async fn dispatch_http_call(request: HttpRequest) -> Result<HttpResponse, &'static str> {
-    let response = dispatch_host_call(request, http_call).await;
-    response.map(|response| serde_json::from_slice::<HttpResponse>(&response[..]).map_err(|_| "Failed to deserialize HttpResponse"))?
+pub async fn dispatch_http_call(request: HttpRequest) -> Result<HttpResponse, HttpError> {
+    let serialized = dispatch_host_call(request, http_call).await?;
+    let response = decode_call_result::<HttpError>(&serialized)??;
+    serde_json::from_slice::<HttpResponse>(&response[..]).map_err(|_| "Failed to deserialize HttpResponse".into())
+}
+
+/// Streamed http response body (see `HttpRequest::with_stream`); closed when dropped.
+pub struct HttpResponseBody {
+    handle: u32,
+    closed: bool,
+}
+
+impl HttpResponseBody {
+    pub fn new(response: &HttpResponse) -> Option<Self> {
+        response.body_handle.map(|handle| HttpResponseBody { handle, closed: false })
+    }
+
+    /// Reads up to `max_len` bytes; an empty chunk means the body has been fully read.
+    pub async fn read_chunk(&mut self, max_len: u32) -> Result<Vec<u8>, HttpError> {
+        let handle = self.handle;
+        let serialized = await_host_callback(|callback_id| unsafe {
+            http_read_body(handle, max_len, callback_id)
+        }).await?;
+        let chunk = decode_call_result::<HttpError>(&serialized)??;
+        Ok(chunk)
+    }
+
+    pub fn close(mut self) -> Result<(), HttpError> {
+        self.closed = true;
+        match unsafe { http_close(self.handle) } {
+            0 => Ok(()),
+            _ => Err(HttpError::InvalidRequest("unknown body handle".into())),
+        }
+    }
+}
+
+impl Drop for HttpResponseBody {
+    fn drop(&mut self) {
+        if !self.closed {
+            unsafe { http_close(self.handle) };
+        }
+    }
+}
+
+impl From<HostCallError> for S3Error {
+    fn from(e: HostCallError) -> Self {
+        match e {
+            HostCallError::QuotaExceeded => S3Error::QuotaExceeded,
+            HostCallError::Failed(e) => e.into(),
+        }
+    }
+}
+
+pub async fn dispatch_s3_call(request: S3Command) -> Result<Vec<u8>, S3Error> {
+    dispatch_s3_call_counted(request).await.result
+}
+
+/// Like `dispatch_s3_call`, also reporting the number of network attempts the runtime made (see `RetryPolicy`).
+pub async fn dispatch_s3_call_counted(request: S3Command) -> CallResult<S3Error> {
+    match dispatch_counted_host_call(request, s3_call).await {
+        Ok((serialized, attempts)) => decode_counted_result(&serialized, attempts),
+        Err(e) => CallResult { result: Err(e.into()), attempts: 0 },
+    }
+}
+
+/// Lists a page of objects; continue with `next_continuation_token` while the response `is_truncated`.
+pub async fn dispatch_s3_list(opts: S3ListOpts) -> Result<S3ListItemResponse, S3Error> {
+    let response = dispatch_s3_call(S3Command::S3List(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize S3ListItemResponse".into())
+}
+
+/// Fetches an object (or a range of it); a conditional request that did not match responds with status 304.
+pub async fn dispatch_s3_get(opts: S3GetOpts) -> Result<S3Response, S3Error> {
+    let response = dispatch_s3_call(S3Command::S3Get(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize S3Response".into())
+}
+
+/// Creates a temporary url for an object; the runtime signs it, the guest never sees the secret key.
+pub async fn dispatch_s3_presign(opts: S3PresignOpts) -> Result<S3PresignedUrl, S3Error> {
+    let response = dispatch_s3_call(S3Command::S3Presign(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize S3PresignedUrl".into())
+}
+
+pub async fn dispatch_s3_head_object(opts: S3HeadObjectOpts) -> Result<S3ObjectMetadata, S3Error> {
+    let response = dispatch_s3_call(S3Command::S3HeadObject(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize S3ObjectMetadata".into())
+}
+
+pub async fn dispatch_s3_list_buckets(opts: S3ListBucketsOpts) -> Result<S3ListBucketsResponse, S3Error> {
+    let response = dispatch_s3_call(S3Command::S3ListBuckets(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize S3ListBucketsResponse".into())
+}
+
+pub async fn dispatch_s3_head_bucket(opts: S3HeadBucketOpts) -> Result<S3HeadBucketResponse, S3Error> {
+    let response = dispatch_s3_call(S3Command::S3HeadBucket(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize S3HeadBucketResponse".into())
+}
+
+pub async fn dispatch_s3_copy_object(opts: S3CopyObjectOpts) -> Result<S3CopyObjectResponse, S3Error> {
+    let response = dispatch_s3_call(S3Command::S3CopyObject(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize S3CopyObjectResponse".into())
+}
+
+/// Deletes up to 1000 objects at once; objects that could not be deleted are listed in `errors`.
+pub async fn dispatch_s3_delete_objects(opts: S3DeleteObjectsOpts) -> Result<S3DeleteObjectsResponse, S3Error> {
+    let response = dispatch_s3_call(S3Command::S3DeleteObjects(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize S3DeleteObjectsResponse".into())
+}
+
+pub use bls_common::s3::S3_MIN_PART_SIZE;
+
+/// Uploads an object from a stream of chunks in parts of `part_size` bytes (at least `S3_MIN_PART_SIZE`),
+/// so the whole object never has to be held in memory. The upload is aborted when any step fails.
+pub async fn s3_multipart_upload(
+    config: S3Config,
+    bucket_name: &str,
+    path: &str,
+    content_type: Option<String>,
+    part_size: usize,
+    mut chunks: impl Stream<Item = Result<Vec<u8>, S3Error>> + Unpin,
+) -> Result<S3CompleteMultipartUploadResponse, S3Error> {
+    let part_size = part_size.max(S3_MIN_PART_SIZE);
+    let response = dispatch_s3_call(S3Command::S3CreateMultipartUpload(S3CreateMultipartUploadOpts {
+        config: config.clone(),
+        bucket_name: bucket_name.into(),
+        path: path.into(),
+        content_type,
+    })).await?;
+    let upload: S3MultipartUpload = serde_json::from_slice(&response)
+        .map_err(|_| "Failed to deserialize S3MultipartUpload")?;
+
+    let upload_part = |part_number: u32, content: Vec<u8>| {
+        let opts = S3UploadPartOpts {
+            config: config.clone(),
+            bucket_name: bucket_name.into(),
+            path: path.into(),
+            upload_id: upload.upload_id.clone(),
+            part_number,
+            content,
+        };
+        async move {
+            let response = dispatch_s3_call(S3Command::S3UploadPart(opts)).await?;
+            serde_json::from_slice::<S3CompletedPart>(&response).map_err(|_| S3Error::from("Failed to deserialize S3CompletedPart"))
+        }
+    };
+
+    let uploaded = async {
+        let mut parts = vec![];
+        let mut buffer = vec![];
+        while let Some(chunk) = chunks.next().await {
+            buffer.extend(chunk?);
+            while buffer.len() >= part_size {
+                let rest = buffer.split_off(part_size);
+                let part = std::mem::replace(&mut buffer, rest);
+                parts.push(upload_part(parts.len() as u32 + 1, part).await?);
+            }
+        }
+        // the last part may be smaller (or even empty for an empty object)
+        if !buffer.is_empty() || parts.is_empty() {
+            parts.push(upload_part(parts.len() as u32 + 1, buffer).await?);
+        }
+
+        let response = dispatch_s3_call(S3Command::S3CompleteMultipartUpload(S3CompleteMultipartUploadOpts {
+            config: config.clone(),
+            bucket_name: bucket_name.into(),
+            path: path.into(),
+            upload_id: upload.upload_id.clone(),
+            parts,
+        })).await?;
+        serde_json::from_slice::<S3CompleteMultipartUploadResponse>(&response)
+            .map_err(|_| "Failed to deserialize S3CompleteMultipartUploadResponse".into())
+    }.await;
+
+    if uploaded.is_err() {
+        // best effort - the original error is more useful to the caller
+        let _ = dispatch_s3_call(S3Command::S3AbortMultipartUpload(S3AbortMultipartUploadOpts {
+            config,
+            bucket_name: bucket_name.into(),
+            path: path.into(),
+            upload_id: upload.upload_id,
+        })).await;
+    }
+    uploaded
+}
+
+/// Runs an IPFS command; errors carry the message of the runtime (e.g. a denied permission or a failed
+/// verification of gateway content).
+pub async fn dispatch_ipfs_call(request: IPFSCommand) -> Result<Vec<u8>, String> {
+    dispatch_ipfs_call_counted(request).await.result
+}
+
+/// Like `dispatch_ipfs_call`, also reporting the number of network attempts the runtime made (see `RetryPolicy`).
+pub async fn dispatch_ipfs_call_counted(request: IPFSCommand) -> CallResult<String> {
+    match dispatch_counted_host_call(request, ipfs_call).await {
+        Ok((serialized, attempts)) => decode_counted_result(&serialized, attempts),
+        Err(e) => CallResult { result: Err(<&'static str>::from(e).into()), attempts: 0 },
+    }
+}
+
+/// Lists a directory of the node's mutable file system.
+pub async fn dispatch_ipfs_files_ls(opts: FilesLsOpts) -> Result<FilesLsResponse, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::FilesLs(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize FilesLsResponse".into())
+}
+
+pub async fn dispatch_ipfs_files_stat(opts: FilesStatOpts) -> Result<FilesStatResponse, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::FilesStat(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize FilesStatResponse".into())
+}
+
+pub async fn dispatch_ipfs_version(opts: VersionOpts) -> Result<VersionResponse, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::Version(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize VersionResponse".into())
+}
+
+/// Adds the files; responds with one entry per file (and the wrapping directory last, if requested).
+pub async fn dispatch_ipfs_add(opts: AddOpts, files: Vec<AddFile>) -> Result<Vec<AddResponse>, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::Add(opts, files)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize AddResponse".into())
+}
+
+/// Lists the links of a directory by CID or IPFS path.
+pub async fn dispatch_ipfs_ls(opts: LsOpts) -> Result<LsResponse, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::Ls(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize LsResponse".into())
+}
+
+/// Pins content on the node so it is retained, e.g. after writing it through `FilesWrite`.
+pub async fn dispatch_ipfs_pin_add(opts: PinAddOpts) -> Result<PinResponse, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::PinAdd(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize PinResponse".into())
+}
+
+pub async fn dispatch_ipfs_pin_ls(opts: PinLsOpts) -> Result<PinLsResponse, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::PinLs(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize PinLsResponse".into())
+}
+
+pub async fn dispatch_ipfs_pin_rm(opts: PinRmOpts) -> Result<PinResponse, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::PinRm(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize PinResponse".into())
+}
+
+/// Moves a pin to new content; only the blocks that differ are fetched.
+pub async fn dispatch_ipfs_pin_update(opts: PinUpdateOpts) -> Result<PinResponse, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::PinUpdate(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize PinResponse".into())
+}
+
+/// Requests a pin at the configured remote pinning service; the pin is `queued` until the service has the content.
+pub async fn dispatch_ipfs_pin_remote_add(opts: PinRemoteAddOpts) -> Result<RemotePin, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::PinRemoteAdd(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize RemotePin".into())
 }
 
-pub async fn dispatch_s3_call(request: S3Command) -> Result<Vec<u8>, &'static str> {
-    dispatch_host_call(request, s3_call).await
+pub async fn dispatch_ipfs_pin_remote_ls(opts: PinRemoteLsOpts) -> Result<PinRemoteLsResponse, String> {
+    let response = dispatch_ipfs_call(IPFSCommand::PinRemoteLs(opts)).await?;
+    serde_json::from_slice(&response).map_err(|_| "Failed to deserialize PinRemoteLsResponse".into())
 }
 
-pub async fn dispatch_ipfs_call(request: IPFSCommand) -> Result<Vec<u8>, &'static str> {
-    dispatch_host_call(request, ipfs_call).await
+pub async fn dispatch_ipfs_pin_remote_rm(opts: PinRemoteRmOpts) -> Result<(), String> {
+    dispatch_ipfs_call(IPFSCommand::PinRemoteRm(opts)).await.map(|_| ())
 }
 
 #[no_mangle]
@@ -251,6 +600,10 @@ mod tests {
                     secret_key: "test".to_string(),
                     endpoint: "http://localhost:4566".to_string(),
                     region: None,
+                    retry: None,
+                    session_token: None,
+                    profile: None,
+                    addressing_style: Default::default(),
                 },
                 bucket_name: "my-new-bucket".to_string(),
             })).await;
@@ -264,13 +617,68 @@ mod tests {
     #[test]
     fn test_ipfs_call() {
         executor::spawn_local(async {
-            let files_ls = IPFSCommand::FilesLs(FilesLsOpts::default());
-            let result = dispatch_ipfs_call(files_ls).await;
-            let res_str = String::from_utf8(result.unwrap()).unwrap();
-            log!("{:?}", res_str);
+            let result = dispatch_ipfs_files_ls(FilesLsOpts::default()).await;
+            log!("{:?}", result.unwrap().entries);
         });  
     }
 
+    /// Serializes a result the way the runtime passes it to the callbacks.
+    fn callback_payload<T: Serialize, E: Serialize>(result: Result<T, E>) -> Vec<u8> {
+        serde_json::to_vec(&result.map(|value| serde_json::to_vec(&value).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn test_decode_http_payload() {
+        let response = HttpResponse {
+            status: 200,
+            headers: Default::default(),
+            body: vec![],
+            body_handle: Some(1),
+            attempts: 2,
+        };
+        let payload = callback_payload::<_, HttpError>(Ok(&response));
+        let decoded = decode_call_result::<HttpError>(&payload).unwrap().unwrap();
+        assert_eq!(serde_json::from_slice::<HttpResponse>(&decoded).unwrap(), response);
+
+        let payload = callback_payload::<HttpResponse, _>(Err(HttpError::Timeout(5000)));
+        assert_eq!(decode_call_result::<HttpError>(&payload).unwrap(), Err(HttpError::Timeout(5000)));
+        assert!(decode_call_result::<HttpError>(b"not json").is_err());
+    }
+
+    #[test]
+    fn test_decode_s3_payload() {
+        let upload = S3MultipartUpload {
+            bucket_name: "my-new-bucket".into(),
+            path: "big-object.bin".into(),
+            upload_id: "upload-1".into(),
+        };
+        let call = decode_counted_result::<S3Error>(&callback_payload::<_, S3Error>(Ok(&upload)), 3);
+        assert_eq!(call.attempts, 3);
+        assert_eq!(serde_json::from_slice::<S3MultipartUpload>(&call.result.unwrap()).unwrap(), upload);
+
+        let call = decode_counted_result::<S3Error>(&callback_payload::<(), _>(Err(S3Error::PermissionDenied)), 1);
+        assert_eq!((call.result, call.attempts), (Err(S3Error::PermissionDenied), 1));
+
+        // a malformed payload is a dispatch error; the attempts were still made
+        let call = decode_counted_result::<S3Error>(b"not json", 2);
+        assert_eq!((call.result, call.attempts), (Err(S3Error::Dispatch("Failed to deserialize call result".into())), 2));
+    }
+
+    #[test]
+    fn test_decode_ipfs_payload() {
+        let added = vec![AddResponse { name: "hello.txt".into(), hash: "bafkreihello".into(), size: 12 }];
+        let call = decode_counted_result::<String>(&callback_payload::<_, String>(Ok(&added)), 1);
+        assert_eq!(call.attempts, 1);
+        assert_eq!(serde_json::from_slice::<Vec<AddResponse>>(&call.result.unwrap()).unwrap(), added);
+
+        let pinned = PinResponse { pins: vec!["bafkreihello".into()] };
+        let call = decode_counted_result::<String>(&callback_payload::<_, String>(Ok(&pinned)), 0);
+        assert_eq!(serde_json::from_slice::<PinResponse>(&call.result.unwrap()).unwrap(), pinned);
+
+        let call = decode_counted_result::<String>(&callback_payload::<(), _>(Err("permission denied".to_string())), 0);
+        assert_eq!(call.result, Err("permission denied".to_string()));
+    }
+
     #[test]
     fn test_wasm32_wasi_file_write() {
         // // write hello world to a file (my-file.txt)
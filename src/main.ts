@@ -26,7 +26,6 @@ const bls = new Blockless({
         "https://jsonplaceholder.typicode.com/todos/7",
         "https://jsonplaceholder.typicode.com/todos/8",
         "http://httpbin.org/anything",
-        "file://a.go"
     ],
     // fs,
 });